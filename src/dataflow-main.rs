@@ -1,14 +1,40 @@
-use std::collections::HashMap;
-use std::process::exit;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::builder::RangedU64ValueParser;
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 use derive_builder::Builder;
-use tree_sitter::{Node, Parser};
+use serde::Serialize;
+use tracing::{debug, info, warn};
+use tree_sitter::Node;
 
-use crate::dataflow::java::build_graph;
+use dataflow_experiments::dataflow::callgraph::build_call_graph;
+use dataflow_experiments::dataflow::common::get_code_for_node;
+use dataflow_experiments::dataflow::errors::DataflowError;
+use dataflow_experiments::dataflow::files::{get_source_files, read_source, FileFilter};
+use dataflow_experiments::dataflow::hierarchy::TypeHierarchy;
+use dataflow_experiments::dataflow::index::{CodeIndex, DEFAULT_INDEX_FILE};
+use dataflow_experiments::dataflow::java::{build_project_graph, FileTypes};
+use dataflow_experiments::dataflow::languages::Language;
+use dataflow_experiments::dataflow::library::Library;
+use dataflow_experiments::dataflow::logging::init_logging;
+use dataflow_experiments::dataflow::model::{Container, DataFlow};
+use dataflow_experiments::dataflow::resources::find_resource_leaks;
+use dataflow_experiments::dataflow::rules::RuleSet;
+use dataflow_experiments::dataflow::slice::{backward_slice, find_node, forward_slice, is_criterion_file, Slice, SliceCriterion, SliceOptions};
+use dataflow_experiments::dataflow::taint::{find_tainted_flows, TaintOptions, MAX_CONTEXT_DEPTH};
 
-mod dataflow;
+/// Exit code of a run that reports findings (tainted flows, resource leaks or query matches).
+const EXIT_FINDINGS: u8 = 1;
+/// Exit code of a run that fails, also used by clap for invalid arguments.
+const EXIT_ERROR: u8 = 2;
 
+/// Default query of the `query` command: the parameters of the servlets carrying a request.
 const SOURCE_QUERY: &str = r#"(method_declaration
     name: (identifier) @name
     parameters: (formal_parameters
@@ -22,14 +48,150 @@ const SOURCE_QUERY: &str = r#"(method_declaration
     (#any-of? @name "doGet" "doPost" "doPatch")
 )"#;
 
+/// Dataflow analysis of Java code.
+///
+/// Exits with 0 when the analysis reports nothing, 1 when it reports findings and 2 on errors,
+/// including the files that cannot be analyzed.
+#[derive(Parser)]
+#[command(name = "dataflow")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Additional rules, loaded after the bundled rules
+    #[arg(long, global = true, value_name = "RULES.json")]
+    rules: Vec<String>,
+
+    /// Format of the output
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Write the output to a file instead of the standard output
+    #[arg(short, long, global = true, value_name = "FILE")]
+    output: Option<String>,
+
+    /// Language of the files, detected from their name, extension or shebang by default;
+    /// only the query command supports other languages than Java
+    #[arg(long, global = true, value_enum)]
+    language: Option<Language>,
+
+    /// Only analyze the files of the directories matching a glob (e.g. 'src/main/**')
+    #[arg(long, global = true, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Do not analyze the files of the directories matching a glob (e.g. '**/test/**')
+    #[arg(long, global = true, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Log the progress of the analysis on the standard error: -v for info, -vv for debug, -vvv for trace
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Level or filter of the logs (e.g. debug or warn,dataflow_experiments::dataflow::taint=trace),
+    /// also read from the DATAFLOW_LOG environment variable
+    #[arg(long, global = true, value_name = "LEVEL")]
+    log_level: Option<String>,
+
+    /// Only print the results, without the summaries
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the dataflow graph of the files
+    Graph(PathArgs),
+    /// Report the tainted flows between the sources and the sinks of the rules
+    Scan {
+        /// Also report the resources that are not closed on all paths
+        #[arg(long)]
+        resources: bool,
+
+        /// Number of call sites distinguishing the calls of a method
+        #[arg(long, value_name = "DEPTH", default_value_t = TaintOptions::default().context_depth, value_parser = RangedU64ValueParser::<usize>::new().range(0..=MAX_CONTEXT_DEPTH as u64))]
+        context: usize,
+
+        #[command(flatten)]
+        paths: PathArgs,
+    },
+    /// Print the nodes and statements a value depends on, or that depend on it with --forward
+    Slice {
+        /// Follow the flows from the value instead of the flows into it
+        #[arg(long)]
+        forward: bool,
+
+        /// Maximum number of edges between the value and the nodes of the slice
+        #[arg(long)]
+        depth: Option<usize>,
+
+        /// Only keep the nodes of a function
+        #[arg(long, value_name = "FUNCTION")]
+        container: Option<String>,
+
+        /// Files or directories to analyze
+        #[arg(required = true, value_name = "PATH")]
+        paths: Vec<String>,
+
+        /// Value the slice starts from, as [file:]line:column or function:name
+        criterion: String,
+    },
+    /// Print the matches of a tree-sitter query
+    Query {
+        /// File of the query, the servlet requests by default
+        #[arg(long, value_name = "QUERY.scm")]
+        query: Option<String>,
+
+        #[command(flatten)]
+        paths: PathArgs,
+    },
+    /// Print the calls between the methods and the calls that are not resolved
+    Callgraph(PathArgs),
+    /// Print the subclasses of a class or the implementations of an interface
+    Hierarchy {
+        /// Fully qualified name of a class whose subclasses are printed (e.g. javax.servlet.http.HttpServlet)
+        #[arg(long, value_name = "CLASS", required_unless_present = "implementations", conflicts_with = "implementations")]
+        subclasses: Option<String>,
+
+        /// Fully qualified name of an interface whose concrete implementations are printed
+        #[arg(long, value_name = "INTERFACE")]
+        implementations: Option<String>,
+
+        #[command(flatten)]
+        paths: PathArgs,
+    },
+    /// Print the number of types, methods and calls of the files
+    Index(PathArgs),
+    /// Print the definitions and the references of a name from an index written by index-code
+    Lookup {
+        /// File of the index
+        #[arg(long, value_name = "FILE", default_value = DEFAULT_INDEX_FILE)]
+        index: PathBuf,
+
+        /// Simple or qualified name (e.g. findUser or com.example.dao.UserDao.findUser)
+        name: String,
+    },
+}
+
+#[derive(Args)]
+struct PathArgs {
+    /// Files or directories to analyze
+    #[arg(required = true, value_name = "PATH")]
+    paths: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
 
 #[derive(Clone, Debug, Builder)]
 pub struct MatchNode<'node> {
     pub captures: HashMap<String, Node<'node>>,
 }
 
-fn get_query(query_code: &str, language: &tree_sitter::Language) -> Result<tree_sitter::Query> {
-    Ok(tree_sitter::Query::new(&language, query_code)?)
+fn get_query(query_code: &str, language: &tree_sitter::Language) -> Result<tree_sitter::Query, DataflowError> {
+    tree_sitter::Query::new(language, query_code).map_err(|source| DataflowError::Query { source })
 }
 
 fn get_query_nodes<'tree>(tree: &'tree tree_sitter::Tree, query: &tree_sitter::Query, code: &str) -> Vec<MatchNode<'tree>> {
@@ -49,44 +211,731 @@ fn get_query_nodes<'tree>(tree: &'tree tree_sitter::Tree, query: &tree_sitter::Q
                 .get(usize::try_from(capture.index).unwrap());
 
             if let Some(capture_name) = capture_name_opt {
-                captures.captures.insert(capture_name.to_string(), capture.node.clone());
+                captures.captures.insert(capture_name.to_string(), capture.node);
             }
         }
 
         matches.push(captures);
     }
 
-    return matches;
+    matches
+}
+
+/// The parsed files of the paths given on the command line.
+struct Project {
+    filenames: Vec<String>,
+    sources: Vec<String>,
+    trees: Vec<tree_sitter::Tree>,
+    // the errors of the files that are not analyzed
+    failures: Vec<anyhow::Error>,
+}
+
+/// Read and parse a file in a language.
+fn parse_file(filename: &str, language: Language) -> Result<(String, tree_sitter::Tree), DataflowError> {
+    let source_code = read_source(filename)?;
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&language.get_tree_sitter_language()).map_err(|source| DataflowError::Language {
+        language: language.get_name().to_string(),
+        source,
+    })?;
+    let tree = parser.parse(&source_code, None).ok_or_else(|| DataflowError::Parse { path: filename.to_string() })?;
+    Ok((source_code, tree))
+}
+
+impl Project {
+    /// Parse the files of a language, the files given on the command line in another
+    /// language are not analyzed.
+    fn load(cli: &Cli, paths: &[String], language: Language) -> Result<Project> {
+        let filter = FileFilter::new(&cli.include, &cli.exclude).context("invalid --include or --exclude glob")?;
+        let filenames = get_source_files(paths, &filter, cli.language).context("error while listing the files")?;
+        let mut project = Project {
+            filenames: vec![],
+            sources: vec![],
+            trees: vec![],
+            failures: vec![],
+        };
+        for (filename, file_language) in filenames {
+            let result = match file_language {
+                Some(file_language) if file_language == language => parse_file(&filename, language).map_err(anyhow::Error::new),
+                // the files of the other languages in the directories
+                Some(file_language) if !paths.contains(&filename) => {
+                    debug!(file = filename.as_str(), language = file_language.get_name(), "skipped");
+                    continue;
+                }
+                Some(file_language) => Err(anyhow!("{} is a {} file, only the {} files are analyzed", filename, file_language.get_name(), language.get_name())),
+                None => Err(anyhow::Error::new(DataflowError::UnknownLanguage { path: filename.clone() })),
+            };
+            match result {
+                Ok((source_code, tree)) => {
+                    info!(file = filename.as_str(), "parsed");
+                    project.filenames.push(filename);
+                    project.sources.push(source_code);
+                    project.trees.push(tree);
+                }
+                Err(error) => {
+                    warn!("{:#}, the file is skipped", error);
+                    project.failures.push(error);
+                }
+            }
+        }
+        Ok(project)
+    }
+
+    /// Build the graph of all the files, with a FILE container for each file.
+    fn build_graph<'a>(&'a self, library: &'a Library) -> DataFlow<'a> {
+        let files: Vec<(&str, &str, &tree_sitter::Tree)> = self.filenames.iter().zip(self.sources.iter()).zip(self.trees.iter()).map(|((f, s), t)| (f.as_str(), s.as_str(), t)).collect();
+        build_project_graph(&files, library)
+    }
+
+    fn get_source(&self, filename: &str) -> &str {
+        self.filenames.iter().position(|f| f == filename).map(|i| self.sources[i].as_str()).unwrap_or_default()
+    }
+}
+
+#[derive(Serialize)]
+struct NodeReport {
+    name: String,
+    kind: String,
+    line: usize,
+    column: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    type_name: Option<String>,
+    sanitized: bool,
+    // names of the nodes the value flows to
+    outbound: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ContainerReport {
+    name: String,
+    kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    qualified_name: Option<String>,
+    containers: Vec<ContainerReport>,
+    nodes: Vec<NodeReport>,
+}
+
+impl From<&Container<'_>> for ContainerReport {
+    fn from(container: &Container) -> Self {
+        ContainerReport {
+            name: container.name.clone().unwrap_or_default(),
+            kind: format!("{:?}", container.kind),
+            qualified_name: container.qualified_name.clone(),
+            containers: container.containers.iter().map(|c| ContainerReport::from(c.as_ref())).collect(),
+            nodes: container
+                .nodes
+                .iter()
+                .map(|n| {
+                    let (line, column) = n.get_position();
+                    NodeReport {
+                        name: n.name.clone().unwrap_or_default(),
+                        kind: format!("{:?}", n.kind),
+                        line,
+                        column,
+                        type_name: n.type_name.clone(),
+                        sanitized: n.sanitized,
+                        outbound: n.outbound.read().unwrap().iter().map(|o| o.name.clone().unwrap_or_default()).collect(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PathStepReport {
+    file: String,
+    line: usize,
+    column: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edge: Option<String>,
+    node: String,
+    kind: String,
+}
+
+#[derive(Serialize)]
+struct FindingReport {
+    file: String,
+    line: usize,
+    column: usize,
+    vulnerability: String,
+    label: String,
+    source: String,
+    source_rule: String,
+    argument: usize,
+    method: String,
+    function: String,
+    path: Vec<PathStepReport>,
+}
+
+#[derive(Serialize)]
+struct ResourceLeakReport {
+    file: String,
+    line: usize,
+    column: usize,
+    type_name: String,
+    name: String,
+    function: String,
+}
+
+#[derive(Serialize)]
+struct ScanReport {
+    findings: Vec<FindingReport>,
+    resource_leaks: Vec<ResourceLeakReport>,
+}
+
+#[derive(Serialize)]
+struct SliceNodeReport {
+    file: String,
+    line: usize,
+    column: usize,
+    node: String,
+    kind: String,
+    function: String,
+    depth: usize,
+}
+
+#[derive(Serialize)]
+struct StatementReport {
+    file: String,
+    line: usize,
+    code: String,
+}
+
+#[derive(Serialize)]
+struct SliceReport {
+    nodes: Vec<SliceNodeReport>,
+    statements: Vec<StatementReport>,
+}
+
+#[derive(Serialize)]
+struct CaptureReport {
+    line: usize,
+    column: usize,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct MatchReport {
+    file: String,
+    captures: BTreeMap<String, CaptureReport>,
+}
+
+#[derive(Serialize)]
+struct TypeReport {
+    name: String,
+    kind: String,
+    file: String,
+}
+
+#[derive(Serialize)]
+struct CallReport {
+    file: String,
+    line: usize,
+    column: usize,
+    caller: String,
+    callee: String,
+    kind: String,
+    // the reason why the callee is not resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unresolved: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DefinitionReport {
+    file: String,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    kind: String,
+    qualified_name: String,
+}
+
+#[derive(Serialize)]
+struct ReferenceReport {
+    file: String,
+    line: usize,
+    column: usize,
+    kind: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LookupReport {
+    definitions: Vec<DefinitionReport>,
+    references: Vec<ReferenceReport>,
+}
+
+#[derive(Serialize)]
+struct IndexReport {
+    files: usize,
+    types: usize,
+    methods: usize,
+    calls: usize,
+    unresolved_calls: usize,
+}
+
+fn write_json<T: Serialize>(out: &mut dyn Write, report: &T) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *out, report)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Print the graph of the files.
+fn print_graph(out: &mut dyn Write, format: Format, dataflow: &DataFlow) -> Result<()> {
+    match format {
+        Format::Text => dataflow.print_graph(out)?,
+        Format::Json => {
+            let containers: Vec<ContainerReport> = dataflow.containers.iter().map(|c| ContainerReport::from(c.as_ref())).collect();
+            write_json(out, &containers)?;
+        }
+    }
+    Ok(())
+}
+
+/// Print the nodes of a slice and their statements, each with the file containing it.
+fn print_slice(out: &mut dyn Write, format: Format, slice: &Slice, project: &Project) -> Result<()> {
+    let report = SliceReport {
+        nodes: slice
+            .nodes
+            .iter()
+            .map(|slice_node| {
+                let (line, column) = slice_node.node.get_position();
+                SliceNodeReport {
+                    file: slice_node.file.clone(),
+                    line,
+                    column,
+                    node: slice_node.node.name.clone().unwrap_or_default(),
+                    kind: format!("{:?}", slice_node.node.kind),
+                    function: slice_node.function.clone(),
+                    depth: slice_node.depth,
+                }
+            })
+            .collect(),
+        statements: slice
+            .statements
+            .iter()
+            .map(|s| StatementReport {
+                file: s.file.clone(),
+                line: s.statement.start_position().row + 1,
+                code: get_code_for_node(s.statement, project.get_source(&s.file)).lines().next().unwrap_or_default().trim().to_string(),
+            })
+            .collect(),
+    };
+
+    if format == Format::Json {
+        return write_json(out, &report);
+    }
+    for node in &report.nodes {
+        writeln!(out, "{}:{}:{}: {} ({}) in {} at depth {}", node.file, node.line, node.column, node.node, node.kind, node.function, node.depth)?;
+    }
+    writeln!(out, "statements:")?;
+    for statement in &report.statements {
+        writeln!(out, "{}:{}: {}", statement.file, statement.line, statement.code)?;
+    }
+    Ok(())
 }
 
-fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <filename>", args[0]);
-        std::process::exit(1);
+/// Print the calls between the methods of several files and the calls that are not resolved.
+fn print_call_graph(out: &mut dyn Write, format: Format, dataflow: &DataFlow) -> Result<()> {
+    let call_graph = build_call_graph(dataflow);
+    let mut calls = vec![];
+    for (call, targets) in &call_graph.calls {
+        let (line, column) = call.node.get_position();
+        for target in targets {
+            calls.push(CallReport {
+                file: call.file.clone(),
+                line,
+                column,
+                caller: call.caller.to_string(),
+                callee: target.to_string(),
+                kind: format!("{:?}", call.invocation.kind),
+                unresolved: None,
+            });
+        }
+    }
+    for (call, reason) in &call_graph.unresolved {
+        let (line, column) = call.node.get_position();
+        calls.push(CallReport {
+            file: call.file.clone(),
+            line,
+            column,
+            caller: call.caller.to_string(),
+            callee: format!("{}.{}/{}", call.invocation.type_name.clone().unwrap_or("?".to_string()), call.invocation.method, call.invocation.arguments),
+            kind: format!("{:?}", call.invocation.kind),
+            unresolved: Some(format!("{:?}", reason)),
+        });
     }
 
-    let filename = &args[1];
+    if format == Format::Json {
+        return write_json(out, &calls);
+    }
+    for call in &calls {
+        match &call.unresolved {
+            Some(reason) => writeln!(out, "{}:{}:{}: {} -> {} unresolved ({})", call.file, call.line, call.column, call.caller, call.callee, reason)?,
+            None => writeln!(out, "{}:{}:{}: {} -> {} ({})", call.file, call.line, call.column, call.caller, call.callee, call.kind)?,
+        }
+    }
+    Ok(())
+}
 
-    // read filename into a string
-    let source_code = std::fs::read_to_string(filename).expect("error while reading file");
+/// Print the subclasses of a class or the implementations of an interface.
+fn print_hierarchy(out: &mut dyn Write, format: Format, dataflow: &DataFlow, subclasses: &Option<String>, implementations: &Option<String>) -> Result<()> {
+    let hierarchy = TypeHierarchy::from_dataflow(dataflow);
+    let names = match (subclasses, implementations) {
+        (Some(class), _) => hierarchy.get_subclasses(class),
+        (None, Some(interface)) => hierarchy.get_implementations(interface),
+        (None, None) => vec![],
+    };
+    let types: Vec<TypeReport> = names
+        .into_iter()
+        .map(|name| {
+            let declaration = &hierarchy.types[&name];
+            TypeReport {
+                kind: format!("{:?}", declaration.kind),
+                file: declaration.file.clone(),
+                name,
+            }
+        })
+        .collect();
 
+    if format == Format::Json {
+        return write_json(out, &types);
+    }
+    for type_report in &types {
+        writeln!(out, "{}: {} {}", type_report.file, type_report.kind, type_report.name)?;
+    }
+    Ok(())
+}
 
-    let mut parser = Parser::new();
-    parser.set_language(&tree_sitter_java::language()).expect("error while loading Java language");
-    let tree = parser.parse(&source_code, None).expect("error while parsing source code");
-    let code_str = source_code.as_str();
-    build_graph(&tree, code_str);
+/// Find the tainted flows and, when requested, the resource leaks of all the files.
+fn scan(project: &Project, dataflow: &DataFlow, rules: &RuleSet, library: &Library, check_resources: bool, taint_options: &TaintOptions) -> ScanReport {
+    let mut resource_leaks = vec![];
+    if check_resources {
+        let hierarchy = Arc::new(TypeHierarchy::from_dataflow(dataflow));
+        for (filename, (tree, code_str)) in project.filenames.iter().zip(project.trees.iter().zip(project.sources.iter())) {
+            let types = FileTypes::new(tree, code_str, library, hierarchy.clone());
+            for leak in find_resource_leaks(tree.root_node(), code_str, &types) {
+                resource_leaks.push(ResourceLeakReport {
+                    file: filename.clone(),
+                    line: leak.line,
+                    column: leak.column,
+                    type_name: leak.type_name,
+                    name: leak.name,
+                    function: leak.function,
+                });
+            }
+        }
+    }
 
+    let call_graph = build_call_graph(dataflow);
+    let findings: Vec<FindingReport> = find_tainted_flows(dataflow, &call_graph, rules, library, taint_options)
+        .iter()
+        .map(|finding| {
+            let (line, column) = finding.sink.get_position();
+            let invocation = finding.sink.invocation.clone().unwrap();
+            FindingReport {
+                file: finding.file.clone(),
+                line,
+                column,
+                vulnerability: finding.sink_rule.clone(),
+                label: finding.label.clone(),
+                source: finding.source.name.clone().unwrap_or_default(),
+                source_rule: finding.source_rule.clone(),
+                argument: invocation.argument.unwrap_or_default(),
+                method: invocation.method,
+                function: finding.function.clone(),
+                path: finding
+                    .path
+                    .iter()
+                    .map(|step| {
+                        let (line, column) = step.node.get_position();
+                        PathStepReport {
+                            file: step.file.clone(),
+                            line,
+                            column,
+                            edge: step.edge.map(|e| format!("{:?}", e)),
+                            node: step.node.name.clone().unwrap_or_default(),
+                            kind: format!("{:?}", step.node.kind),
+                        }
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
 
-    let source_query = get_query(SOURCE_QUERY, &tree_sitter_java::language()).expect("get source query");
-    let nodes = get_query_nodes(&tree, &source_query, code_str);
+    ScanReport { findings, resource_leaks }
+}
 
-    if nodes.len() == 0 {
-        println!("no node");
-        exit(1);
+/// Print the tainted flows and the resource leaks, returns true if there are any.
+fn print_scan(out: &mut dyn Write, cli: &Cli, files: usize, report: &ScanReport) -> Result<bool> {
+    let has_findings = !report.findings.is_empty() || !report.resource_leaks.is_empty();
+    if cli.format == Format::Json {
+        write_json(out, report)?;
+        return Ok(has_findings);
     }
 
-    let nodes_len = nodes.len();
-    println!("Found {} matches", nodes_len);
+    for leak in &report.resource_leaks {
+        writeln!(out, "{}:{}:{}: {} {} opened in {} is not closed on all paths", leak.file, leak.line, leak.column, leak.type_name, leak.name, leak.function)?;
+    }
+    for finding in &report.findings {
+        writeln!(
+            out,
+            "{}:{}:{}: {} [{}]: {} from {} flows into argument {} of {} in {}",
+            finding.file, finding.line, finding.column, finding.vulnerability, finding.label, finding.source, finding.source_rule, finding.argument, finding.method, finding.function
+        )?;
+        for step in &finding.path {
+            let edge = step.edge.clone().map(|e| format!("{} ", e)).unwrap_or_default();
+            writeln!(out, "    {}:{}:{}: {}{} ({})", step.file, step.line, step.column, edge, step.node, step.kind)?;
+        }
+    }
+    if !cli.quiet {
+        writeln!(out, "{} findings, {} resource leaks in {} files", report.findings.len(), report.resource_leaks.len(), files)?;
+    }
+    Ok(has_findings)
+}
+
+/// Print the matches of a query in all the files, returns true if there are any.
+fn query(out: &mut dyn Write, cli: &Cli, project: &Project, language: Language, query_file: &Option<String>) -> Result<bool> {
+    let query_code = match query_file {
+        Some(query_file) => read_source(query_file)?,
+        None => SOURCE_QUERY.to_string(),
+    };
+    let query = get_query(&query_code, &language.get_tree_sitter_language()).with_context(|| format!("error in {}", query_file.as_deref().unwrap_or("the default query")))?;
+
+    let mut matches = vec![];
+    for ((filename, tree), code_str) in project.filenames.iter().zip(project.trees.iter()).zip(project.sources.iter()) {
+        for query_match in get_query_nodes(tree, &query, code_str) {
+            let captures = query_match
+                .captures
+                .iter()
+                .map(|(name, node)| {
+                    let position = node.start_position();
+                    let capture = CaptureReport {
+                        line: position.row + 1,
+                        column: position.column + 1,
+                        text: get_code_for_node(*node, code_str),
+                    };
+                    (name.clone(), capture)
+                })
+                .collect();
+            matches.push(MatchReport { file: filename.clone(), captures });
+        }
+    }
+
+    if cli.format == Format::Json {
+        write_json(out, &matches)?;
+        return Ok(!matches.is_empty());
+    }
+    for query_match in &matches {
+        for (name, capture) in &query_match.captures {
+            writeln!(out, "{}:{}:{}: @{} {}", query_match.file, capture.line, capture.column, name, capture.text.lines().next().unwrap_or_default())?;
+        }
+    }
+    if !cli.quiet {
+        writeln!(out, "Found {} matches", matches.len())?;
+    }
+    Ok(!matches.is_empty())
+}
+
+/// Print the number of types, methods and calls of the files.
+fn index(out: &mut dyn Write, format: Format, project: &Project, dataflow: &DataFlow) -> Result<()> {
+    let call_graph = build_call_graph(dataflow);
+    let report = IndexReport {
+        files: project.filenames.len(),
+        types: call_graph.hierarchy.types.len(),
+        methods: call_graph.methods.len(),
+        calls: call_graph.calls.len() + call_graph.unresolved.len(),
+        unresolved_calls: call_graph.unresolved.len(),
+    };
+    match format {
+        Format::Json => write_json(out, &report),
+        Format::Text => {
+            writeln!(
+                out,
+                "{} files, {} types, {} methods, {} calls ({} unresolved)",
+                report.files, report.types, report.methods, report.calls, report.unresolved_calls
+            )?;
+            Ok(())
+        }
+    }
+}
+
+/// Print the definitions and the references of a name, without parsing the files.
+fn lookup(out: &mut dyn Write, format: Format, index_file: &Path, name: &str) -> Result<()> {
+    let index = CodeIndex::load(index_file)?;
+    let report = LookupReport {
+        definitions: index
+            .find_definitions(name)
+            .iter()
+            .map(|(file, definition)| DefinitionReport {
+                file: file.path.clone(),
+                line: definition.line,
+                column: definition.column,
+                end_line: definition.end_line,
+                kind: definition.kind.clone(),
+                qualified_name: definition.qualified_name.clone(),
+            })
+            .collect(),
+        references: index
+            .find_references(name)
+            .iter()
+            .map(|(file, reference)| ReferenceReport {
+                file: file.path.clone(),
+                line: reference.line,
+                column: reference.column,
+                kind: reference.kind.clone(),
+                name: reference.name.clone(),
+                container: file.get_container_name(reference.container).map(|c| c.to_string()),
+            })
+            .collect(),
+    };
+
+    if format == Format::Json {
+        return write_json(out, &report);
+    }
+    writeln!(out, "definitions:")?;
+    for definition in &report.definitions {
+        writeln!(out, "{}:{}:{}: {} {}", definition.file, definition.line, definition.column, definition.kind, definition.qualified_name)?;
+    }
+    writeln!(out, "references:")?;
+    for reference in &report.references {
+        let container = reference.container.clone().map(|c| format!(" in {}", c)).unwrap_or_default();
+        writeln!(out, "{}:{}:{}: {} {}{}", reference.file, reference.line, reference.column, reference.kind, reference.name, container)?;
+    }
+    Ok(())
+}
+
+/// The result of a command on the files that could be analyzed.
+struct Outcome {
+    has_findings: bool,
+    files: usize,
+    failures: Vec<anyhow::Error>,
+}
+
+/// Run a command on all the files that can be analyzed.
+fn run(cli: &Cli) -> Result<Outcome> {
+    let mut out: Box<dyn Write> = match &cli.output {
+        Some(output) => Box::new(BufWriter::new(File::create(output).with_context(|| format!("error while creating {}", output))?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    // the index is read instead of the files
+    if let Command::Lookup { index, name } = &cli.command {
+        lookup(&mut out, cli.format, index, name)?;
+        out.flush()?;
+        return Ok(Outcome {
+            has_findings: false,
+            files: 0,
+            failures: vec![],
+        });
+    }
+
+    let mut rules = RuleSet::bundled().context("error while loading the bundled rules")?;
+    for rules_file in &cli.rules {
+        rules.extend(RuleSet::from_file(rules_file)?);
+    }
+    let mut library = Library::bundled().context("error while loading the library models")?;
+    rules.add_to(&mut library);
+
+    let paths = match &cli.command {
+        Command::Graph(paths) | Command::Callgraph(paths) | Command::Index(paths) => &paths.paths,
+        Command::Scan { paths, .. } | Command::Query { paths, .. } | Command::Hierarchy { paths, .. } => &paths.paths,
+        Command::Slice { paths, .. } => paths,
+        Command::Lookup { .. } => unreachable!("the index is looked up without the files"),
+    };
+    // the graph is only built for Java, the other languages can only be queried
+    let language = cli.language.unwrap_or(Language::Java);
+    let is_query = matches!(cli.command, Command::Query { .. });
+    if language != Language::Java && !is_query {
+        bail!("the {} files can only be queried, the other commands analyze Java", language.get_name());
+    }
+    let project = Project::load(cli, paths, language)?;
+    let dataflow = match language {
+        Language::Java => project.build_graph(&library),
+        _ => DataFlow::default(),
+    };
+    info!(files = project.filenames.len(), "graph built");
+
+    let has_findings = match &cli.command {
+        Command::Graph(_) => {
+            print_graph(&mut out, cli.format, &dataflow)?;
+            false
+        }
+        Command::Scan { resources, context, .. } => {
+            let taint_options = TaintOptions { context_depth: *context };
+            let report = scan(&project, &dataflow, &rules, &library, *resources, &taint_options);
+            print_scan(&mut out, cli, project.filenames.len(), &report)?
+        }
+        Command::Slice { forward, depth, container, criterion, .. } => {
+            let slice_criterion = SliceCriterion::try_from(criterion.as_str())?;
+            match &slice_criterion {
+                SliceCriterion::Position { file: Some(file), .. } if !project.filenames.iter().any(|f| is_criterion_file(f, file)) => {
+                    bail!("{} is not an analyzed file", file);
+                }
+                SliceCriterion::Position { file: None, .. } if project.filenames.len() > 1 => {
+                    bail!("the position {} must give the file among the analyzed files", criterion);
+                }
+                _ => {}
+            }
+            let Some(node) = find_node(&dataflow, &slice_criterion) else {
+                bail!("no node for {}", criterion);
+            };
+            let slice_options = SliceOptions {
+                depth: *depth,
+                container: container.clone(),
+            };
+            let slice = match forward {
+                true => forward_slice(&dataflow, &node, &slice_options),
+                false => backward_slice(&dataflow, &node, &slice_options),
+            };
+            print_slice(&mut out, cli.format, &slice, &project)?;
+            false
+        }
+        Command::Query { query: query_file, .. } => query(&mut out, cli, &project, language, query_file)?,
+        Command::Hierarchy { subclasses, implementations, .. } => {
+            print_hierarchy(&mut out, cli.format, &dataflow, subclasses, implementations)?;
+            false
+        }
+        Command::Callgraph(_) => {
+            print_call_graph(&mut out, cli.format, &dataflow)?;
+            false
+        }
+        Command::Index(_) => {
+            index(&mut out, cli.format, &project, &dataflow)?;
+            false
+        }
+        Command::Lookup { .. } => unreachable!("the index is looked up without the files"),
+    };
+    out.flush()?;
+    Ok(Outcome {
+        has_findings,
+        files: project.filenames.len(),
+        failures: project.failures,
+    })
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Err(error) = init_logging(cli.log_level.as_deref(), cli.verbose) {
+        eprintln!("error: invalid log level: {:#}", error);
+        return ExitCode::from(EXIT_ERROR);
+    }
+    match run(&cli) {
+        Ok(outcome) if !outcome.failures.is_empty() => {
+            eprintln!("error: {} files analyzed, {} could not be analyzed:", outcome.files, outcome.failures.len());
+            for failure in &outcome.failures {
+                eprintln!("  {:#}", failure);
+            }
+            ExitCode::from(EXIT_ERROR)
+        }
+        Ok(outcome) if outcome.has_findings => ExitCode::from(EXIT_FINDINGS),
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {:#}", error);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
 }
@@ -1,4 +1,19 @@
+
 pub mod java;
+pub mod callgraph;
+pub mod hierarchy;
 pub mod common;
-mod model;
-
+pub mod errors;
+pub mod files;
+pub mod index;
+pub mod languages;
+pub mod library;
+pub mod logging;
+pub mod collections;
+pub mod resources;
+pub mod rules;
+pub mod slice;
+pub mod taint;
+pub mod model;
+#[cfg(test)]
+pub mod testing;
@@ -0,0 +1,538 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use tracing::debug;
+
+use crate::dataflow::hierarchy::TypeHierarchy;
+use crate::dataflow::model::{CallKind, Container, ContainerKind, DataFlow, Invocation, Node, NodeKind};
+
+/// Methods inherited by all the types from `java.lang.Object`.
+const OBJECT_METHODS: [&str; 9] = ["equals", "hashCode", "toString", "getClass", "clone", "finalize", "notify", "notifyAll", "wait"];
+
+/// A method of the analyzed code, identified by its type, its name (`<init>` for
+/// a constructor, `<instinit>` and `<clinit>` for the initializers) and its number of parameters.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MethodId {
+    pub type_name: String,
+    pub name: String,
+    pub parameters: usize,
+}
+
+impl fmt::Display for MethodId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}/{}", self.type_name, self.name, self.parameters)
+    }
+}
+
+pub struct MethodDeclaration<'a> {
+    pub id: MethodId,
+    pub container: Arc<Container<'a>>,
+    pub is_static: bool,
+    // without a body: abstract or interface method
+    pub is_abstract: bool,
+    // the last parameter is a variable arity parameter
+    pub is_varargs: bool,
+}
+
+impl MethodDeclaration<'_> {
+    fn accepts(&self, arguments: usize) -> bool {
+        self.id.parameters == arguments || (self.is_varargs && arguments + 1 >= self.id.parameters)
+    }
+}
+
+/// A call of the analyzed code, with its CALL node.
+pub struct CallSite<'a> {
+    // the method (or initializer) containing the call
+    pub caller: MethodId,
+    pub file: String,
+    pub node: Arc<Node<'a>>,
+    pub invocation: Invocation,
+}
+
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnresolvedReason {
+    // the type of the receiver is not known
+    UNKNOWN_TYPE,
+    // the method is declared by a type that is not part of the analyzed code (e.g. the JDK)
+    EXTERNAL_TYPE,
+    // the type is part of the analyzed code but does not declare the method
+    UNKNOWN_METHOD,
+}
+
+pub struct CallGraph<'a> {
+    pub methods: Vec<MethodDeclaration<'a>>,
+    // the resolved calls with their possible targets
+    pub calls: Vec<(CallSite<'a>, Vec<MethodId>)>,
+    pub unresolved: Vec<(CallSite<'a>, UnresolvedReason)>,
+    pub hierarchy: TypeHierarchy,
+    // index in methods of each method
+    method_indexes: HashMap<MethodId, usize>,
+    // indexes in methods of the overloads of each type and method name
+    overloads: HashMap<(String, String), Vec<usize>>,
+    // indexes in calls of the calls that may invoke each method
+    callers: HashMap<MethodId, Vec<usize>>,
+}
+
+impl<'a> CallGraph<'a> {
+    pub fn get_method(&self, id: &MethodId) -> Option<&MethodDeclaration<'a>> {
+        self.method_indexes.get(id).map(|i| &self.methods[*i])
+    }
+
+    /// Returns the calls that may invoke a method.
+    pub fn get_callers(&self, callee: &MethodId) -> Vec<&CallSite<'a>> {
+        self.callers.get(callee).map_or(vec![], |indexes| indexes.iter().map(|i| &self.calls[*i].0).collect())
+    }
+
+    /// Add a method to the graph; the overloads with the same number of parameters share
+    /// their id, which designates the first one.
+    fn add_method(&mut self, declaration: MethodDeclaration<'a>) {
+        let index = self.methods.len();
+        self.method_indexes.entry(declaration.id.clone()).or_insert(index);
+        self.overloads.entry((declaration.id.type_name.clone(), declaration.id.name.clone())).or_default().push(index);
+        self.methods.push(declaration);
+    }
+
+    fn add_call(&mut self, call: CallSite<'a>, targets: Vec<MethodId>) {
+        for target in &targets {
+            self.callers.entry(target.clone()).or_default().push(self.calls.len());
+        }
+        self.calls.push((call, targets));
+    }
+
+    fn get_overloads(&self, type_name: &str, name: &str) -> impl Iterator<Item = &MethodDeclaration<'a>> {
+        let indexes = self.overloads.get(&(type_name.to_string(), name.to_string()));
+        indexes.into_iter().flatten().map(|i| &self.methods[*i])
+    }
+
+    fn find_declared(&self, type_name: &str, name: &str, arguments: usize) -> Option<&MethodDeclaration<'a>> {
+        self.get_overloads(type_name, name).find(|m| m.accepts(arguments))
+    }
+
+    /// Returns the method invoked on a type, declared by the type or inherited, with the
+    /// implementations preferred to the abstract declarations.
+    fn find_inherited(&self, type_name: &str, name: &str, arguments: usize) -> Option<&MethodDeclaration<'a>> {
+        let declarations: Vec<&MethodDeclaration> = self.hierarchy.get_ancestors(type_name).iter().filter_map(|t| self.find_declared(t, name, arguments)).collect();
+        declarations.iter().find(|d| !d.is_abstract).or(declarations.first()).copied()
+    }
+
+    fn resolve(&self, invocation: &Invocation) -> Result<Vec<MethodId>, UnresolvedReason> {
+        let type_name = invocation.type_name.as_ref().ok_or(UnresolvedReason::UNKNOWN_TYPE)?;
+        if !self.hierarchy.contains(type_name) {
+            return Err(UnresolvedReason::EXTERNAL_TYPE);
+        }
+
+        let targets: Vec<MethodId> = match invocation.kind {
+            CallKind::CONSTRUCTOR => {
+                let has_constructors = self.get_overloads(type_name, "<init>").next().is_some();
+                match self.find_declared(type_name, &invocation.method, invocation.arguments) {
+                    Some(constructor) => vec![constructor.id.clone()],
+                    // the default constructor
+                    None if !has_constructors && invocation.arguments == 0 => vec![MethodId {
+                        type_name: type_name.clone(),
+                        name: "<init>".to_string(),
+                        parameters: 0,
+                    }],
+                    None => vec![],
+                }
+            }
+            CallKind::STATIC | CallKind::SUPER => self.find_inherited(type_name, &invocation.method, invocation.arguments).map(|m| m.id.clone()).into_iter().collect(),
+            // class hierarchy analysis: the inherited method and all the overriding methods of the subtypes
+            CallKind::VIRTUAL => {
+                let inherited = self.find_inherited(type_name, &invocation.method, invocation.arguments);
+                let mut targets: Vec<MethodId> = inherited.map(|m| m.id.clone()).into_iter().collect();
+                // a static method is hidden by the methods of the subtypes, not overridden
+                let subtypes = match inherited.is_some_and(|m| m.is_static) {
+                    true => vec![],
+                    false => self.hierarchy.get_subtypes(type_name),
+                };
+                for subtype in subtypes {
+                    if let Some(method) = self.find_declared(&subtype, &invocation.method, invocation.arguments).filter(|m| !m.is_abstract) {
+                        if !targets.contains(&method.id) {
+                            targets.push(method.id.clone());
+                        }
+                    }
+                }
+                // an abstract declaration is not a target when there are implementations
+                if targets.len() > 1 {
+                    targets.retain(|t| self.get_method(t).is_some_and(|m| !m.is_abstract));
+                }
+                targets
+            }
+        };
+
+        if !targets.is_empty() {
+            return Ok(targets);
+        }
+
+        // a method inherited from java.lang.Object or from a type outside of the analyzed code
+        let has_external_ancestor = self.hierarchy.get_ancestors(type_name).iter().any(|t| !self.hierarchy.contains(t));
+        if OBJECT_METHODS.contains(&invocation.method.as_str()) || has_external_ancestor {
+            return Err(UnresolvedReason::EXTERNAL_TYPE);
+        }
+        Err(UnresolvedReason::UNKNOWN_METHOD)
+    }
+}
+
+fn get_method_declaration<'a>(container: &Arc<Container<'a>>, type_name: &str) -> MethodDeclaration<'a> {
+    let name = match container.kind {
+        ContainerKind::CONSTRUCTOR => "<init>".to_string(),
+        _ => container.name.clone().unwrap_or_default(),
+    };
+    let ts_node = container.ts_node;
+    // the variable arity parameters are not nodes of the graph, count the declared parameters
+    let parameters = match ts_node.and_then(|n| n.child_by_field_name("parameters")) {
+        Some(declared) => {
+            let mut cursor = declared.walk();
+            let count = declared.named_children(&mut cursor).filter(|c| c.grammar_name() == "formal_parameter" || c.grammar_name() == "spread_parameter").count();
+            count
+        }
+        None => container.nodes.iter().filter(|n| matches!(n.kind, NodeKind::PARAMETER)).count(),
+    };
+    let has_child = |node: Option<tree_sitter::Node>, grammar_name: &str| {
+        node.is_some_and(|n| {
+            let mut cursor = n.walk();
+            let found = n.children(&mut cursor).any(|c| c.grammar_name() == grammar_name);
+            found
+        })
+    };
+    let modifiers = ts_node.and_then(|n| {
+        let mut cursor = n.walk();
+        let modifiers = n.named_children(&mut cursor).find(|c| c.grammar_name() == "modifiers");
+        modifiers
+    });
+    let is_static = has_child(modifiers, "static");
+    let is_abstract = ts_node.is_some_and(|n| n.child_by_field_name("body").is_none());
+    let is_varargs = has_child(ts_node.and_then(|n| n.child_by_field_name("parameters")), "spread_parameter");
+
+    MethodDeclaration {
+        id: MethodId {
+            type_name: type_name.to_string(),
+            name,
+            parameters,
+        },
+        container: container.clone(),
+        is_static,
+        is_abstract,
+        is_varargs,
+    }
+}
+
+/// Returns the static or the instance initializer of a type, which runs its initializer blocks
+/// and the initializers of its fields.
+fn get_initializer_declaration<'a>(container: &Arc<Container<'a>>, type_name: &str, is_static: bool) -> MethodDeclaration<'a> {
+    MethodDeclaration {
+        id: MethodId {
+            type_name: type_name.to_string(),
+            name: if is_static { "<clinit>" } else { "<instinit>" }.to_string(),
+            parameters: 0,
+        },
+        container: container.clone(),
+        is_static,
+        is_abstract: false,
+        is_varargs: false,
+    }
+}
+
+/// Returns true if a node of a type body is in the initializer of a static field: a field
+/// declared `static`, a constant of an interface or the arguments of an enum constant.
+fn is_in_static_field(node: tree_sitter::Node) -> bool {
+    let declaration = std::iter::successors(Some(node), |n| n.parent()).find(|n| matches!(n.grammar_name(), "field_declaration" | "constant_declaration" | "enum_constant"));
+    match declaration {
+        Some(field) if field.grammar_name() == "field_declaration" => {
+            let mut cursor = field.walk();
+            let modifiers = field.named_children(&mut cursor).find(|c| c.grammar_name() == "modifiers");
+            modifiers.is_some_and(|m| {
+                let mut cursor = m.walk();
+                let found = m.children(&mut cursor).any(|c| c.grammar_name() == "static");
+                found
+            })
+        }
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Collect the types, the methods and the calls of a container and of its nested containers.
+fn collect<'a>(container: &Arc<Container<'a>>, package: &Option<String>, file: &str, current_type: Option<&String>, caller: Option<&MethodId>, graph: &mut CallGraph<'a>, calls: &mut Vec<CallSite<'a>>) {
+    let mut type_name = current_type.cloned();
+    let mut method = caller.cloned();
+    // the calls in the initializers of the fields, run with the static or the instance initializers
+    let mut field_initializers: Vec<MethodDeclaration<'a>> = vec![];
+
+    match container.kind {
+        ContainerKind::FILE => {}
+        ContainerKind::FUNCTION | ContainerKind::CONSTRUCTOR => {
+            let declaration = get_method_declaration(container, &type_name.clone().unwrap_or_default());
+            method = Some(declaration.id.clone());
+            graph.add_method(declaration);
+        }
+        ContainerKind::INITIALIZER => {
+            let is_static = container.name.as_deref() == Some("<clinit>");
+            let declaration = get_initializer_declaration(container, &type_name.clone().unwrap_or_default(), is_static);
+            method = Some(declaration.id.clone());
+            // all the initializer blocks of a type are run by the same initializer
+            if graph.get_method(&declaration.id).is_none() {
+                graph.add_method(declaration);
+            }
+        }
+        _ => {
+            type_name = container.get_full_type_name(package);
+            if let Some(type_name) = &type_name {
+                field_initializers = vec![get_initializer_declaration(container, type_name, false), get_initializer_declaration(container, type_name, true)];
+            }
+            method = field_initializers.first().map(|d| d.id.clone());
+        }
+    }
+
+    let mut used = [false, false];
+    for node in &container.nodes {
+        let (NodeKind::CALL, Some(invocation)) = (&node.kind, &node.invocation) else {
+            continue;
+        };
+        let caller = match field_initializers.is_empty() {
+            true => method.as_ref(),
+            false => {
+                let index = is_in_static_field(*node.ts_node) as usize;
+                used[index] = true;
+                Some(&field_initializers[index].id)
+            }
+        };
+        if let Some(caller) = caller {
+            calls.push(CallSite {
+                caller: caller.clone(),
+                file: file.to_string(),
+                node: node.clone(),
+                invocation: invocation.clone(),
+            });
+        }
+    }
+
+    for child in &container.containers {
+        collect(child, package, file, type_name.as_ref(), method.as_ref(), graph, calls);
+    }
+
+    // after the initializer blocks, which are preferred as the containers of the initializers
+    for (declaration, used) in field_initializers.into_iter().zip(used) {
+        if used && graph.get_method(&declaration.id).is_none() {
+            graph.add_method(declaration);
+        }
+    }
+}
+
+/// Build the call graph of all the files of a graph.
+/// The calls are resolved with the types of the receivers; a virtual call may invoke
+/// the methods overriding the invoked method in all the subtypes (class hierarchy analysis).
+pub fn build_call_graph<'a>(dataflow: &DataFlow<'a>) -> CallGraph<'a> {
+    let mut graph = CallGraph {
+        methods: vec![],
+        calls: vec![],
+        unresolved: vec![],
+        hierarchy: TypeHierarchy::from_dataflow(dataflow),
+        method_indexes: HashMap::new(),
+        overloads: HashMap::new(),
+        callers: HashMap::new(),
+    };
+    let mut calls = vec![];
+
+    for container in &dataflow.containers {
+        let file = container.name.clone().unwrap_or_default();
+        collect(container, &container.package, &file, None, None, &mut graph, &mut calls);
+    }
+
+    for call in calls {
+        match graph.resolve(&call.invocation) {
+            Ok(targets) => graph.add_call(call, targets),
+            Err(reason) => graph.unresolved.push((call, reason)),
+        }
+    }
+    debug!(methods = graph.methods.len(), calls = graph.calls.len(), unresolved = graph.unresolved.len(), "call graph built");
+
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::java::build_project_graph;
+    use crate::dataflow::testing::{bundled_library, parse_java};
+
+    const CODE: &str = r#"
+package app;
+
+interface Shape {
+    double area();
+}
+
+class Square implements Shape {
+    public double area() { return 1; }
+    static Square create() { return new Square(); }
+}
+
+class Circle implements Shape {
+    public double area() { return 3; }
+}
+
+class Unit extends Square {
+    static Square create() { return new Unit(); }
+}
+
+class Main {
+    double total(Shape shape, Object other) {
+        Square.create();
+        // a static method called on an instance is not overridden by Unit.create
+        new Square().create();
+        shape.toString();
+        shape.perimeter();
+        other.hashCode();
+        unknown.run();
+        return shape.area();
+    }
+}
+"#;
+
+    /// Returns the targets of the calls of a method, sorted and without duplicates.
+    fn get_targets(call_graph: &CallGraph, caller: &str, method: &str) -> Vec<String> {
+        let mut targets: Vec<String> = call_graph
+            .calls
+            .iter()
+            .filter(|(call, _)| call.caller.to_string() == caller && call.invocation.method == method)
+            .flat_map(|(_, targets)| targets.iter().map(|t| t.to_string()))
+            .collect();
+        targets.sort();
+        targets.dedup();
+        targets
+    }
+
+    fn get_unresolved(call_graph: &CallGraph, method: &str) -> Vec<UnresolvedReason> {
+        call_graph.unresolved.iter().filter(|(call, _)| call.invocation.method == method).map(|(_, reason)| *reason).collect()
+    }
+
+    #[test]
+    fn a_virtual_call_invokes_the_implementations_of_the_subtypes() {
+        let tree = parse_java(CODE);
+        let dataflow = build_project_graph(&[("Test.java", CODE, &tree)], bundled_library());
+        let call_graph = build_call_graph(&dataflow);
+
+        assert_eq!(get_targets(&call_graph, "app.Main.total/2", "area"), vec!["app.Circle.area/0", "app.Square.area/0"]);
+        let area = MethodId {
+            type_name: "app.Circle".to_string(),
+            name: "area".to_string(),
+            parameters: 0,
+        };
+        let callers: Vec<String> = call_graph.get_callers(&area).iter().map(|c| c.caller.to_string()).collect();
+        assert_eq!(callers, vec!["app.Main.total/2"]);
+    }
+
+    #[test]
+    fn a_static_call_invokes_the_method_of_its_type_only() {
+        let tree = parse_java(CODE);
+        let dataflow = build_project_graph(&[("Test.java", CODE, &tree)], bundled_library());
+        let call_graph = build_call_graph(&dataflow);
+
+        assert_eq!(get_targets(&call_graph, "app.Main.total/2", "create"), vec!["app.Square.create/0"]);
+        // the default constructor
+        assert_eq!(get_targets(&call_graph, "app.Square.create/0", "<init>"), vec!["app.Square.<init>/0"]);
+    }
+
+    #[test]
+    fn the_unresolved_calls_have_a_reason() {
+        let tree = parse_java(CODE);
+        let dataflow = build_project_graph(&[("Test.java", CODE, &tree)], bundled_library());
+        let call_graph = build_call_graph(&dataflow);
+
+        assert_eq!(get_unresolved(&call_graph, "toString"), vec![UnresolvedReason::EXTERNAL_TYPE]);
+        assert_eq!(get_unresolved(&call_graph, "hashCode"), vec![UnresolvedReason::EXTERNAL_TYPE]);
+        assert_eq!(get_unresolved(&call_graph, "perimeter"), vec![UnresolvedReason::UNKNOWN_METHOD]);
+        assert_eq!(get_unresolved(&call_graph, "run"), vec![UnresolvedReason::UNKNOWN_TYPE]);
+    }
+
+    #[test]
+    fn the_instance_initializers_are_not_the_constructor() {
+        let code = r#"
+package app;
+
+class Task {
+    String name = Task.prefix();
+
+    {
+        register();
+    }
+
+    Task() {
+        start();
+    }
+
+    static String prefix() { return "task"; }
+    void register() {}
+    void start() {}
+}
+"#;
+        let tree = parse_java(code);
+        let dataflow = build_project_graph(&[("Test.java", code, &tree)], bundled_library());
+        let call_graph = build_call_graph(&dataflow);
+
+        assert_eq!(get_targets(&call_graph, "app.Task.<instinit>/0", "prefix"), vec!["app.Task.prefix/0"]);
+        assert_eq!(get_targets(&call_graph, "app.Task.<instinit>/0", "register"), vec!["app.Task.register/0"]);
+        assert_eq!(get_targets(&call_graph, "app.Task.<init>/0", "start"), vec!["app.Task.start/0"]);
+        assert!(get_targets(&call_graph, "app.Task.<init>/0", "register").is_empty());
+    }
+
+    #[test]
+    fn the_static_field_initializers_are_run_by_the_static_initializer() {
+        let code = r#"
+package app;
+
+class Task {
+    static final String PREFIX = Task.prefix();
+    String name = Task.suffix();
+
+    static {
+        load();
+    }
+
+    static String prefix() { return "task"; }
+    static String suffix() { return "1"; }
+    static void load() {}
+}
+"#;
+        let tree = parse_java(code);
+        let dataflow = build_project_graph(&[("Test.java", code, &tree)], bundled_library());
+        let call_graph = build_call_graph(&dataflow);
+
+        assert_eq!(get_targets(&call_graph, "app.Task.<clinit>/0", "prefix"), vec!["app.Task.prefix/0"]);
+        assert_eq!(get_targets(&call_graph, "app.Task.<clinit>/0", "load"), vec!["app.Task.load/0"]);
+        assert_eq!(get_targets(&call_graph, "app.Task.<instinit>/0", "suffix"), vec!["app.Task.suffix/0"]);
+        assert!(get_targets(&call_graph, "app.Task.<instinit>/0", "prefix").is_empty());
+    }
+
+    #[test]
+    fn the_initializers_are_methods_of_the_graph() {
+        let code = r#"
+package app;
+
+class Task {
+    static String prefix = Task.prefix();
+    String name = Task.prefix();
+
+    static String prefix() { return "task"; }
+}
+"#;
+        let tree = parse_java(code);
+        let dataflow = build_project_graph(&[("Test.java", code, &tree)], bundled_library());
+        let call_graph = build_call_graph(&dataflow);
+        let id = |name: &str| MethodId {
+            type_name: "app.Task".to_string(),
+            name: name.to_string(),
+            parameters: 0,
+        };
+
+        assert!(call_graph.get_method(&id("<clinit>")).is_some_and(|m| m.is_static && !m.is_abstract));
+        assert!(call_graph.get_method(&id("<instinit>")).is_some_and(|m| !m.is_static && !m.is_abstract));
+        let mut callers: Vec<String> = call_graph.get_callers(&id("prefix")).iter().map(|c| c.caller.to_string()).collect();
+        callers.sort();
+        assert_eq!(callers, vec!["app.Task.<clinit>/0", "app.Task.<instinit>/0"]);
+    }
+}
+
@@ -0,0 +1,256 @@
+use crate::dataflow::common::get_simple_type_name;
+
+/// Types of the collections (and iterators) whose elements are modeled apart from the collection.
+const COLLECTION_TYPES: [&str; 24] = [
+    "Collection",
+    "Iterable",
+    "List",
+    "ArrayList",
+    "LinkedList",
+    "Vector",
+    "Stack",
+    "CopyOnWriteArrayList",
+    "Set",
+    "HashSet",
+    "LinkedHashSet",
+    "TreeSet",
+    "SortedSet",
+    "NavigableSet",
+    "Queue",
+    "Deque",
+    "ArrayDeque",
+    "PriorityQueue",
+    "BlockingQueue",
+    "LinkedBlockingQueue",
+    "Iterator",
+    "ListIterator",
+    "Enumeration",
+    "Stream",
+];
+
+/// Types of the maps, whose keys and values are modeled apart from the map.
+const MAP_TYPES: [&str; 10] = [
+    "Map",
+    "HashMap",
+    "LinkedHashMap",
+    "TreeMap",
+    "SortedMap",
+    "NavigableMap",
+    "ConcurrentMap",
+    "ConcurrentHashMap",
+    "Hashtable",
+    "Properties",
+];
+
+/// Methods that store their arguments in a collection.
+const ELEMENT_WRITE_METHODS: [&str; 11] = [
+    "add",
+    "addFirst",
+    "addLast",
+    "offer",
+    "offerFirst",
+    "offerLast",
+    "push",
+    "set",
+    "addElement",
+    "insertElementAt",
+    "setElementAt",
+];
+
+/// Methods that store all the elements of another collection in a collection.
+const ELEMENT_WRITE_ALL_METHODS: [&str; 1] = ["addAll"];
+
+/// Methods that return an element of a collection, or a view of its elements.
+const ELEMENT_READ_METHODS: [&str; 31] = [
+    "get",
+    "getFirst",
+    "getLast",
+    "first",
+    "last",
+    "element",
+    "peek",
+    "peekFirst",
+    "peekLast",
+    "poll",
+    "pollFirst",
+    "pollLast",
+    "pop",
+    "remove",
+    "removeFirst",
+    "removeLast",
+    "take",
+    "next",
+    "previous",
+    "nextElement",
+    "elementAt",
+    "firstElement",
+    "lastElement",
+    "floor",
+    "ceiling",
+    "higher",
+    "lower",
+    "iterator",
+    "listIterator",
+    "toArray",
+    "stream",
+];
+
+/// Methods that store a value in a map with a key.
+const MAP_WRITE_METHODS: [&str; 3] = ["put", "putIfAbsent", "replace"];
+
+/// Methods that store all the entries of another map in a map.
+const MAP_WRITE_ALL_METHODS: [&str; 1] = ["putAll"];
+
+/// Methods that return a value of a map from its key.
+const MAP_READ_METHODS: [&str; 3] = ["get", "getOrDefault", "remove"];
+
+/// Methods that return the values of a map.
+const MAP_VALUES_METHODS: [&str; 1] = ["values"];
+
+/// Methods that return the keys of a map.
+const MAP_KEYS_METHODS: [&str; 9] = [
+    "keySet",
+    "navigableKeySet",
+    "keys",
+    "firstKey",
+    "lastKey",
+    "floorKey",
+    "ceilingKey",
+    "higherKey",
+    "lowerKey",
+];
+
+/// Methods that return both the keys and the values of a map.
+const MAP_ENTRIES_METHODS: [&str; 1] = ["entrySet"];
+
+/// How a method invocation accesses the elements of a collection, an array or a map.
+#[derive(Debug, PartialEq)]
+pub enum ElementAccess {
+    // the arguments are stored in the collection
+    Write,
+    // the elements of the argument are stored in the collection
+    WriteAll,
+    // an element of the collection is returned
+    Read,
+    // the first argument is a key and the second one the value stored with it
+    MapWrite,
+    // the keys and values of the argument are stored in the map
+    MapWriteAll,
+    // the value of the key given as the first argument is returned
+    MapRead,
+    MapValues,
+    MapKeys,
+    MapEntries,
+}
+
+pub fn is_array_type(type_name: &str) -> bool {
+    type_name.trim_end().ends_with(']') || type_name.contains("...")
+}
+
+pub fn is_map_type(type_name: &str) -> bool {
+    MAP_TYPES.contains(&get_simple_type_name(type_name).as_str())
+}
+
+pub fn is_collection_type(type_name: &str) -> bool {
+    is_array_type(type_name) || is_map_type(type_name) || COLLECTION_TYPES.contains(&get_simple_type_name(type_name).as_str())
+}
+
+/// Returns how the invocation of a method accesses the elements of its receiver, a local
+/// variable or a field of the given type. The invocations on the receivers of other types
+/// (e.g. `Paths.get`) are calls.
+pub fn get_element_access(method_name: &str, receiver_type: &str) -> Option<ElementAccess> {
+    if !is_collection_type(receiver_type) {
+        return None;
+    }
+
+    if is_map_type(receiver_type) {
+        if MAP_WRITE_METHODS.contains(&method_name) {
+            return Some(ElementAccess::MapWrite);
+        }
+        if MAP_WRITE_ALL_METHODS.contains(&method_name) {
+            return Some(ElementAccess::MapWriteAll);
+        }
+        if MAP_VALUES_METHODS.contains(&method_name) {
+            return Some(ElementAccess::MapValues);
+        }
+        if MAP_KEYS_METHODS.contains(&method_name) {
+            return Some(ElementAccess::MapKeys);
+        }
+        if MAP_ENTRIES_METHODS.contains(&method_name) {
+            return Some(ElementAccess::MapEntries);
+        }
+        if MAP_READ_METHODS.contains(&method_name) {
+            return Some(ElementAccess::MapRead);
+        }
+        return None;
+    }
+
+    if ELEMENT_WRITE_METHODS.contains(&method_name) {
+        return Some(ElementAccess::Write);
+    }
+    if ELEMENT_WRITE_ALL_METHODS.contains(&method_name) {
+        return Some(ElementAccess::WriteAll);
+    }
+    if ELEMENT_READ_METHODS.contains(&method_name) {
+        return Some(ElementAccess::Read);
+    }
+
+    None
+}
+
+/// Name of the node of the elements of a collection or an array, or of the
+/// values of a map stored with a key that is not a constant.
+pub fn get_elements_name(name: &str) -> String {
+    format!("{}[]", name)
+}
+
+/// Name of the node of the keys of a map.
+pub fn get_keys_name(name: &str) -> String {
+    format!("{}[keys]", name)
+}
+
+/// Name of the node of the values of a map stored with a constant key
+/// (e.g. `map["key"]` for `map.put("key", value)`).
+pub fn get_constant_key_name(name: &str, key: &str) -> String {
+    format!("{}[{}]", name, key)
+}
+
+/// Returns true if the name designates the values of a map stored with a constant key.
+pub fn is_constant_key_name(name: &str, map_name: &str) -> bool {
+    name.strip_prefix(map_name).is_some_and(|rest| rest.starts_with("[\"") && rest.ends_with("\"]"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_accesses_depend_on_the_type_of_the_receiver() {
+        let list = "List<String>";
+        let map = "java.util.Map<String, String>";
+
+        assert_eq!(get_element_access("add", list), Some(ElementAccess::Write));
+        assert_eq!(get_element_access("get", list), Some(ElementAccess::Read));
+        assert_eq!(get_element_access("get", map), Some(ElementAccess::MapRead));
+        assert_eq!(get_element_access("put", map), Some(ElementAccess::MapWrite));
+        assert_eq!(get_element_access("add", map), None);
+        assert_eq!(get_element_access("get", "Optional<String>"), None);
+    }
+
+    #[test]
+    fn the_arrays_and_the_varargs_are_collections() {
+        assert!(is_collection_type("String[]"));
+        assert!(is_collection_type("byte[][]"));
+        assert!(is_collection_type("String..."));
+        assert!(!is_map_type("String[]"));
+        assert!(is_collection_type("Properties"));
+    }
+
+    #[test]
+    fn the_constant_keys_are_quoted() {
+        assert_eq!(get_constant_key_name("values", "\"name\""), "values[\"name\"]");
+        assert!(is_constant_key_name("values[\"name\"]", "values"));
+        assert!(!is_constant_key_name(&get_elements_name("values"), "values"));
+        assert!(!is_constant_key_name(&get_keys_name("values"), "values"));
+    }
+}
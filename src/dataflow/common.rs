@@ -1,10 +1,15 @@
-use tree_sitter;
-
 pub fn get_code_for_node(node: tree_sitter::Node, code: &str) -> String {
     let slice = &code[node.start_byte()..node.end_byte()];
     slice.to_string()
 }
 
+/// Returns the name of a type without its package and type arguments
+/// (e.g. `java.util.List<String>` gives `List`).
+pub fn get_simple_type_name(type_name: &str) -> String {
+    let without_arguments = type_name.split('<').next().unwrap_or(type_name);
+    without_arguments.rsplit('.').next().unwrap_or(without_arguments).trim().to_string()
+}
+
 /// Returns recursively if a node or one of the sub-node contains a given identifier.
 /// The [node] is the top tree-sitter node of the tree.
 /// [node_value] is the value of the node in the code
@@ -30,7 +35,7 @@ pub fn contains_identifier(node: tree_sitter::Node, node_value: &String, code: &
 
 fn get_nodes_of_type_rec<'a>(node: tree_sitter::Node<'a>, node_type: &str, acc: &mut Vec<tree_sitter::Node<'a>>) {
     if node.grammar_name() == node_type {
-        acc.push(node.clone());
+        acc.push(node);
     }
     let mut cursor = node.walk();
     let children = node.children(&mut cursor);
@@ -45,5 +50,5 @@ fn get_nodes_of_type_rec<'a>(node: tree_sitter::Node<'a>, node_type: &str, acc:
 pub fn get_nodes_of_type<'a>(node: tree_sitter::Node<'a>, node_type: &str) -> Vec<tree_sitter::Node<'a>> {
     let mut res: Vec<tree_sitter::Node> = vec![];
     get_nodes_of_type_rec(node, node_type, &mut res);
-    return res;
+    res
 }
\ No newline at end of file
@@ -0,0 +1,53 @@
+use std::fmt;
+
+/// The errors of the analysis. An error on a file only skips this file, the other
+/// files are still analyzed.
+#[derive(Debug)]
+pub enum DataflowError {
+    // a file cannot be read
+    Io { path: String, source: std::io::Error },
+    // a source file is not valid UTF-8
+    NonUtf8 { path: String },
+    // the language of a file cannot be detected from its name
+    UnknownLanguage { path: String },
+    // the tree-sitter grammar of a language cannot be loaded
+    Language { language: String, source: tree_sitter::LanguageError },
+    // tree-sitter did not return a tree for a file
+    Parse { path: String },
+    // a tree-sitter query does not compile
+    Query { source: tree_sitter::QueryError },
+    // a rules file is not valid
+    Rules { path: String, source: serde_json::Error },
+    // the configuration of a project is not valid
+    Config { path: String, source: serde_json::Error },
+    // an index file cannot be written or read
+    InvalidIndex { path: String, reason: String },
+}
+
+impl fmt::Display for DataflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataflowError::Io { path, .. } => write!(f, "cannot read {}", path),
+            DataflowError::NonUtf8 { path } => write!(f, "{} is not valid UTF-8", path),
+            DataflowError::UnknownLanguage { path } => write!(f, "cannot detect the language of {}", path),
+            DataflowError::Language { language, .. } => write!(f, "cannot load the {} grammar", language),
+            DataflowError::Parse { path } => write!(f, "cannot parse {}", path),
+            DataflowError::Query { .. } => write!(f, "invalid query"),
+            DataflowError::Rules { path, .. } => write!(f, "invalid rules file {}", path),
+            DataflowError::Config { path, .. } => write!(f, "invalid configuration {}", path),
+            DataflowError::InvalidIndex { path, reason } => write!(f, "invalid index {}: {}", path, reason),
+        }
+    }
+}
+
+impl std::error::Error for DataflowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DataflowError::Io { source, .. } => Some(source),
+            DataflowError::Language { source, .. } => Some(source),
+            DataflowError::Query { source } => Some(source),
+            DataflowError::Rules { source, .. } | DataflowError::Config { source, .. } => Some(source),
+            DataflowError::NonUtf8 { .. } | DataflowError::UnknownLanguage { .. } | DataflowError::Parse { .. } | DataflowError::InvalidIndex { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::dataflow::errors::DataflowError;
+use crate::dataflow::languages::{Language, LanguageRegistry, LanguagesConfig};
+
+/// Name of the configuration of a project, at the root of an analyzed directory.
+pub const CONFIG_FILE: &str = ".dataflow.json";
+/// Files larger than this (in bytes) are not analyzed, unless the configuration gives another size.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// Number of bytes read at the start of a file to detect binary and generated files.
+const HEADER_SIZE: usize = 8 * 1024;
+/// Number of lines of the leading comments of a file searched for a marker of generated code.
+const GENERATED_HEADER_LINES: usize = 10;
+
+/// The files of a project to analyze, read from the [CONFIG_FILE] of a directory, e.g.
+/// `{"exclude": ["**/test/**"], "max_file_size": 500000, "languages": {"extensions": {"jav": "java"}}}`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FilesConfig {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub max_file_size: Option<u64>,
+    pub languages: LanguagesConfig,
+}
+
+impl FilesConfig {
+    /// Returns the configuration of a directory, the default one if it has none.
+    pub fn load(directory: &Path) -> Result<FilesConfig, DataflowError> {
+        let path = directory.join(CONFIG_FILE);
+        if !path.is_file() {
+            return Ok(FilesConfig::default());
+        }
+        let filename = path.to_string_lossy().to_string();
+        let content = read_source(&filename)?;
+        serde_json::from_str(&content).map_err(|source| DataflowError::Config { path: filename, source })
+    }
+}
+
+/// Returns the start of a file, None if it cannot be read.
+fn read_header(path: &Path) -> Option<Vec<u8>> {
+    let mut header = vec![0; HEADER_SIZE];
+    // the error is reported when the file is read
+    let length = File::open(path).and_then(|mut f| f.read(&mut header)).ok()?;
+    header.truncate(length);
+    Some(header)
+}
+
+/// Returns true if a file is marked as generated in its leading comments, by `@generated` or
+/// by a line `Code generated ... DO NOT EDIT.` (the convention of Go). The comments and the
+/// strings of the rest of the file are not searched.
+fn is_generated(header: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(header);
+    let is_comment = |line: &str| ["//", "/*", "*", "#", "--"].iter().any(|p| line.starts_with(p));
+    text.lines()
+        .map(str::trim)
+        .skip_while(|l| l.is_empty())
+        .take(GENERATED_HEADER_LINES)
+        .take_while(|l| l.is_empty() || is_comment(l))
+        .any(|l| l.contains("@generated") || (l.contains("Code generated ") && l.ends_with("DO NOT EDIT.")))
+}
+
+/// Returns the files of a directory, sorted, without the files that are ignored by the
+/// `.gitignore` and `.ignore` files, the hidden files, the files that do not pass the filter or
+/// the include and exclude globs of the [CONFIG_FILE], the files larger than the maximum size
+/// and the binary and generated files.
+pub fn get_files(directory: &str, filter: &FileFilter) -> Result<Vec<PathBuf>> {
+    let config = FilesConfig::load(Path::new(directory))?;
+    let config_filter = FileFilter::new(&config.include, &config.exclude)?;
+    let max_file_size = config.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE);
+    let mut files_to_return: Vec<PathBuf> = vec![];
+
+    // the ignore files apply outside of git repositories too
+    for entry in WalkBuilder::new(directory).follow_links(false).require_git(false).build() {
+        let dir_entry = entry?;
+        let entry = dir_entry.path();
+
+        // we only include if this is a file and not a symlink
+        // we should NEVER follow symlink for security reason (an attacker could then
+        // attempt to add a symlink outside the repo and read content outside of the
+        // repo with a custom rule.
+        let should_include = dir_entry.file_type().is_some_and(|t| t.is_file()) && !dir_entry.path_is_symlink();
+        if !should_include {
+            continue;
+        }
+
+        let relative_path = entry.strip_prefix(directory).unwrap_or(entry);
+        if !filter.matches(relative_path) || !config_filter.matches(relative_path) {
+            debug!(file = %entry.display(), "excluded");
+            continue;
+        }
+        if dir_entry.metadata().is_ok_and(|m| m.len() > max_file_size) {
+            debug!(file = %entry.display(), max_file_size, "too large");
+            continue;
+        }
+        match read_header(entry) {
+            Some(header) if header.contains(&0) => {
+                debug!(file = %entry.display(), "binary");
+                continue;
+            }
+            Some(header) if is_generated(&header) => {
+                debug!(file = %entry.display(), "generated");
+                continue;
+            }
+            _ => {}
+        }
+        files_to_return.push(entry.to_path_buf());
+    }
+
+    files_to_return.sort();
+    Ok(files_to_return)
+}
+
+/// Include and exclude globs for the files found in directories, matched against the
+/// path relative to the directory (e.g. `src/main/**/*.java` or `**/generated/**`).
+#[derive(Default)]
+pub struct FileFilter {
+    // when set, only the matching files are kept
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+impl FileFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<FileFilter> {
+        Ok(FileFilter {
+            include: build_glob_set(include)?,
+            exclude: build_glob_set(exclude)?,
+        })
+    }
+
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        self.include.as_ref().is_none_or(|i| i.is_match(relative_path)) && !self.exclude.as_ref().is_some_and(|e| e.is_match(relative_path))
+    }
+}
+
+/// Returns the files of the paths with their language: the files given directly, in the given
+/// language if any, and the files of the directories in a known language (see [get_files]). The
+/// language of the files of a directory is detected with the languages of its [CONFIG_FILE].
+pub fn get_source_files(paths: &[String], filter: &FileFilter, language: Option<Language>) -> Result<Vec<(String, Option<Language>)>> {
+    let mut files = vec![];
+    for path in paths {
+        if !Path::new(path.as_str()).is_dir() {
+            files.push((path.to_string(), language.or(LanguageRegistry::default().detect(Path::new(path.as_str())))));
+            continue;
+        }
+        let registry = LanguageRegistry::new(&FilesConfig::load(Path::new(path.as_str()))?.languages);
+        // the files of the directories in no known language are not source files
+        let directory_files = get_files(path, filter)?
+            .into_iter()
+            .filter_map(|f| registry.detect(&f).map(|l| (f.to_string_lossy().to_string(), Some(l))));
+        files.extend(directory_files);
+    }
+    Ok(files)
+}
+
+/// Returns the content of a source file.
+pub fn read_source(path: &str) -> Result<String, DataflowError> {
+    let content = std::fs::read(path).map_err(|source| DataflowError::Io { path: path.to_string(), source })?;
+    String::from_utf8(content).map_err(|_| DataflowError::NonUtf8 { path: path.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::testing::temp_dir;
+
+    fn get_relative_files(directory: &Path, filter: &FileFilter) -> Vec<String> {
+        let files = get_files(&directory.to_string_lossy(), filter).unwrap();
+        files.iter().map(|f| f.strip_prefix(directory).unwrap().to_string_lossy().to_string()).collect()
+    }
+
+    fn write(directory: &Path, path: &str, content: &[u8]) {
+        let path = directory.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn lists_the_source_files_of_the_directories_and_the_files_given_directly() {
+        let directory = temp_dir("java-files");
+        std::fs::create_dir_all(directory.join("app/web")).unwrap();
+        for file in ["app/Main.java", "app/web/Servlet.java", "app/README.md", "Other.java"] {
+            std::fs::write(directory.join(file), "class A {}").unwrap();
+        }
+        let app = directory.join("app").to_string_lossy().to_string();
+        let other = directory.join("Other.java").to_string_lossy().to_string();
+
+        let files = get_source_files(&[other, app], &FileFilter::default(), None).unwrap();
+
+        let names: Vec<(&str, Option<Language>)> = files.iter().map(|(f, l)| (f.strip_prefix(directory.to_str().unwrap()).unwrap(), *l)).collect();
+        let java = Some(Language::Java);
+        assert_eq!(names, vec![("/Other.java", java), ("/app/Main.java", java), ("/app/web/Servlet.java", java)]);
+    }
+
+    #[test]
+    fn detects_the_markers_of_the_leading_comments() {
+        assert!(is_generated(b"// Code generated by protoc-gen-go. DO NOT EDIT.\npackage main\n"));
+        assert!(is_generated(b"\n/*\n * @generated by the build\n */\nclass Parser {}\n"));
+        assert!(is_generated(b"#!/usr/bin/env python3\n# @generated\nimport os\n"));
+    }
+
+    #[test]
+    fn does_not_detect_the_markers_after_the_leading_comments() {
+        assert!(!is_generated(b"class Generator {\n    String header = \"// @generated\";\n}\n"));
+        assert!(!is_generated(b"package main\n\n// Code generated by protoc-gen-go. DO NOT EDIT.\n"));
+        assert!(!is_generated(b"// the code generated by the parser, DO NOT EDIT it by hand\nclass Parser {}\n"));
+    }
+
+    #[test]
+    fn filters_the_files_of_the_directories_with_include_and_exclude_globs() {
+        let filter = FileFilter::new(&["src/**/*.java".to_string()], &["**/generated/**".to_string()]).unwrap();
+
+        assert!(filter.matches(Path::new("src/main/Task.java")));
+        assert!(!filter.matches(Path::new("src/main/generated/Parser.java")));
+        assert!(!filter.matches(Path::new("build/Task.java")));
+        assert!(FileFilter::default().matches(Path::new("build/Task.java")));
+    }
+
+    #[test]
+    fn the_errors_of_a_source_file_are_typed() {
+        let directory = temp_dir("read-source");
+        let latin1 = directory.join("Latin1.java").to_string_lossy().to_string();
+        std::fs::write(&latin1, b"class Caf\xe9 {}").unwrap();
+        let missing = directory.join("Missing.java").to_string_lossy().to_string();
+
+        assert!(matches!(read_source(&latin1), Err(DataflowError::NonUtf8 { path }) if path == latin1));
+        assert!(matches!(read_source(&missing), Err(DataflowError::Io { path, .. }) if path == missing));
+    }
+
+    #[test]
+    fn skips_the_ignored_binary_generated_and_excluded_files() {
+        let directory = temp_dir("get-files");
+        write(&directory, ".gitignore", b"target/\n");
+        write(&directory, CONFIG_FILE, br#"{"exclude": ["**/test/**"], "max_file_size": 100}"#);
+        write(&directory, "src/Task.java", b"class Task {}");
+        write(&directory, "src/test/TaskTest.java", b"class TaskTest {}");
+        write(&directory, "target/Task.java", b"class Task {}");
+        write(&directory, "src/Parser.java", b"// @generated\nclass Parser {}");
+        write(&directory, "src/Task.class", b"\xca\xfe\xba\xbe\x00\x00");
+        write(&directory, "src/Large.java", &[b' '; 200]);
+
+        assert_eq!(get_relative_files(&directory, &FileFilter::default()), vec!["src/Task.java"]);
+    }
+
+    #[test]
+    fn an_invalid_configuration_is_an_error() {
+        let directory = temp_dir("files-config");
+        write(&directory, CONFIG_FILE, br#"{"max_file_size": "large"}"#);
+
+        assert!(matches!(FilesConfig::load(&directory), Err(DataflowError::Config { .. })));
+    }
+}
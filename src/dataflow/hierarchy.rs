@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use crate::dataflow::model::{Container, ContainerKind, DataFlow};
+
+/// A type of the analyzed code with its direct supertypes.
+pub struct TypeDeclaration {
+    pub kind: ContainerKind,
+    pub file: String,
+    // fully qualified names of the supertypes, they may be outside of the analyzed code (e.g. HttpServlet)
+    pub superclass: Option<String>,
+    pub interfaces: Vec<String>,
+    // a class declared abstract, it cannot implement an interface on its own
+    pub is_abstract: bool,
+}
+
+/// The types declared by all the files of a project, indexed by fully qualified name.
+#[derive(Default)]
+pub struct TypeHierarchy {
+    pub types: HashMap<String, TypeDeclaration>,
+    // the subtypes of each type, direct or not, sorted by name; the supertypes outside of the analyzed code included
+    subtypes: HashMap<String, Vec<String>>,
+}
+
+impl TypeHierarchy {
+    /// Build the hierarchy of the types declared in the files of a graph.
+    pub fn from_dataflow(dataflow: &DataFlow) -> TypeHierarchy {
+        let mut hierarchy = TypeHierarchy::default();
+        for container in &dataflow.containers {
+            let file = container.name.clone().unwrap_or_default();
+            hierarchy.add_container(&file, container, &container.package);
+        }
+        hierarchy.subtypes = hierarchy.compute_subtypes();
+        hierarchy
+    }
+
+    /// Index each type of the analyzed code under all its ancestors, once for all the lookups.
+    fn compute_subtypes(&self) -> HashMap<String, Vec<String>> {
+        let mut subtypes: HashMap<String, Vec<String>> = HashMap::new();
+        for type_name in self.types.keys() {
+            for ancestor in self.get_ancestors(type_name).into_iter().skip(1).filter(|a| a != type_name) {
+                subtypes.entry(ancestor).or_default().push(type_name.clone());
+            }
+        }
+        subtypes.values_mut().for_each(|s| s.sort());
+        subtypes
+    }
+
+    fn add_container(&mut self, file: &str, container: &Container, package: &Option<String>) {
+        let is_type = matches!(
+            container.kind,
+            ContainerKind::CLASS | ContainerKind::INTERFACE | ContainerKind::ENUM | ContainerKind::RECORD | ContainerKind::ANNOTATION
+        );
+        if let (true, Some(type_name)) = (is_type, container.get_full_type_name(package)) {
+            self.types.insert(
+                type_name,
+                TypeDeclaration {
+                    kind: container.kind,
+                    file: file.to_string(),
+                    superclass: container.superclass.clone(),
+                    interfaces: container.interfaces.clone(),
+                    is_abstract: container.ts_node.is_some_and(is_abstract),
+                },
+            );
+        }
+        for child in &container.containers {
+            self.add_container(file, child, package);
+        }
+    }
+
+    pub fn contains(&self, type_name: &str) -> bool {
+        self.types.contains_key(type_name)
+    }
+
+    pub fn is_interface(&self, type_name: &str) -> bool {
+        self.types.get(type_name).is_some_and(|t| matches!(t.kind, ContainerKind::INTERFACE | ContainerKind::ANNOTATION))
+    }
+
+    /// Returns the direct supertypes of a type of the analyzed code.
+    pub fn get_supertypes(&self, type_name: &str) -> Vec<String> {
+        match self.types.get(type_name) {
+            Some(declaration) => declaration.superclass.iter().chain(declaration.interfaces.iter()).cloned().collect(),
+            None => vec![],
+        }
+    }
+
+    /// Returns a type followed by all its supertypes, the closest first.
+    pub fn get_ancestors(&self, type_name: &str) -> Vec<String> {
+        let mut ancestors = vec![type_name.to_string()];
+        let mut current = 0;
+        while current < ancestors.len() {
+            for supertype in self.get_supertypes(&ancestors[current]) {
+                if !ancestors.contains(&supertype) {
+                    ancestors.push(supertype);
+                }
+            }
+            current += 1;
+        }
+        ancestors
+    }
+
+    /// Returns true if a type is a supertype itself or extends or implements it, directly or not.
+    pub fn is_subtype(&self, type_name: &str, supertype: &str) -> bool {
+        self.get_ancestors(type_name).iter().any(|t| t == supertype)
+    }
+
+    /// Returns the types of the analyzed code that extend or implement a type, directly or not.
+    pub fn get_subtypes(&self, type_name: &str) -> Vec<String> {
+        self.subtypes.get(type_name).cloned().unwrap_or_default()
+    }
+
+    /// Returns the classes extending a class through their superclasses, directly or not (e.g. all
+    /// the servlets of a project for `javax.servlet.http.HttpServlet`). An interface has no subclasses.
+    pub fn get_subclasses(&self, class: &str) -> Vec<String> {
+        if self.is_interface(class) {
+            return vec![];
+        }
+        let mut subclasses: Vec<String> = self
+            .types
+            .keys()
+            .filter(|t| *t != class && !self.is_interface(t) && self.get_superclasses(t).iter().any(|s| s == class))
+            .cloned()
+            .collect();
+        subclasses.sort();
+        subclasses
+    }
+
+    /// Returns the concrete classes, enums and records implementing an interface, directly, through
+    /// their superclasses or through the interfaces extending it. A class has no implementations.
+    pub fn get_implementations(&self, interface: &str) -> Vec<String> {
+        if self.contains(interface) && !self.is_interface(interface) {
+            return vec![];
+        }
+        self.get_subtypes(interface).into_iter().filter(|t| !self.is_interface(t) && !self.types[t].is_abstract).collect()
+    }
+
+    /// Returns the superclasses of a type of the analyzed code, the closest first.
+    fn get_superclasses(&self, type_name: &str) -> Vec<String> {
+        let mut superclasses: Vec<String> = vec![];
+        let mut current = self.types.get(type_name).and_then(|t| t.superclass.clone());
+        while let Some(superclass) = current.filter(|s| !superclasses.contains(s)) {
+            current = self.types.get(&superclass).and_then(|t| t.superclass.clone());
+            superclasses.push(superclass);
+        }
+        superclasses
+    }
+}
+
+/// Returns true if a type declaration has the `abstract` modifier.
+fn is_abstract(node: tree_sitter::Node) -> bool {
+    let mut cursor = node.walk();
+    let modifiers = node.named_children(&mut cursor).find(|c| c.grammar_name() == "modifiers");
+    modifiers.is_some_and(|m| {
+        let mut cursor = m.walk();
+        let found = m.children(&mut cursor).any(|c| c.grammar_name() == "abstract");
+        found
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::java::build_project_graph;
+    use crate::dataflow::testing::{bundled_library, parse_java};
+
+    const CODE: &str = r#"
+package app;
+
+interface Task { void run(); }
+
+interface Job extends Task {}
+
+abstract class BaseTask implements Task {}
+
+class Backup extends BaseTask { public void run() {} }
+
+class Cleanup implements Job { public void run() {} }
+
+class Nightly extends Backup {}
+
+class Servlet extends javax.servlet.http.HttpServlet {}
+"#;
+
+    fn get_hierarchy() -> TypeHierarchy {
+        let tree = parse_java(CODE);
+        let dataflow = build_project_graph(&[("Test.java", CODE, &tree)], bundled_library());
+        TypeHierarchy::from_dataflow(&dataflow)
+    }
+
+    #[test]
+    fn the_subclasses_extend_a_class_directly_or_not() {
+        let hierarchy = get_hierarchy();
+
+        assert_eq!(hierarchy.get_subclasses("app.BaseTask"), vec!["app.Backup", "app.Nightly"]);
+        assert_eq!(hierarchy.get_subclasses("javax.servlet.http.HttpServlet"), vec!["app.Servlet"]);
+        assert!(hierarchy.get_subclasses("app.Nightly").is_empty());
+        // an interface is implemented, not extended
+        assert!(hierarchy.get_subclasses("app.Task").is_empty());
+    }
+
+    #[test]
+    fn the_implementations_of_an_interface_are_the_concrete_classes() {
+        let hierarchy = get_hierarchy();
+
+        assert_eq!(hierarchy.get_implementations("app.Task"), vec!["app.Backup", "app.Cleanup", "app.Nightly"]);
+        assert_eq!(hierarchy.get_implementations("app.Job"), vec!["app.Cleanup"]);
+        // a class has no implementations
+        assert!(hierarchy.get_implementations("app.BaseTask").is_empty());
+    }
+
+    #[test]
+    fn the_subtypes_extend_or_implement_a_type_directly_or_not() {
+        let hierarchy = get_hierarchy();
+
+        assert_eq!(hierarchy.get_subtypes("app.Task"), vec!["app.Backup", "app.BaseTask", "app.Cleanup", "app.Job", "app.Nightly"]);
+        assert_eq!(hierarchy.get_subtypes("app.Backup"), vec!["app.Nightly"]);
+        // a type outside of the analyzed code
+        assert_eq!(hierarchy.get_subtypes("javax.servlet.http.HttpServlet"), vec!["app.Servlet"]);
+        assert!(hierarchy.get_subtypes("app.Nightly").is_empty());
+    }
+}
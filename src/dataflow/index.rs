@@ -0,0 +1,410 @@
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tree_sitter::Tree;
+
+use crate::dataflow::common::get_code_for_node;
+use crate::dataflow::errors::DataflowError;
+
+/// Written at the start of an index file, followed by the version of the format.
+const INDEX_MAGIC: &[u8; 4] = b"DFIX";
+/// Version of the format of the index, an index of another version must be rebuilt.
+pub const INDEX_VERSION: u32 = 2;
+/// Name of the index written by `index-code` in the indexed directory.
+pub const DEFAULT_INDEX_FILE: &str = ".dataflow-index";
+
+/// The tree-sitter nodes that are indexed for a language.
+struct LanguageIndex {
+    // names of the languages sharing the grammar
+    languages: &'static [&'static str],
+    // node of the package (or module) declaration, whose first named child is the name
+    package: Option<&'static str>,
+    // nodes declaring names, with the kind of definition
+    definitions: &'static [(&'static str, &'static str)],
+    // nodes using names, with the field of the name and the kind of reference
+    references: &'static [(&'static str, &'static str, &'static str)],
+}
+
+const LANGUAGES: [LanguageIndex; 5] = [
+    LanguageIndex {
+        languages: &["java"],
+        package: Some("package_declaration"),
+        definitions: &[
+            ("class_declaration", "class"),
+            ("interface_declaration", "interface"),
+            ("enum_declaration", "enum"),
+            ("record_declaration", "record"),
+            ("annotation_type_declaration", "annotation"),
+            ("method_declaration", "method"),
+            ("constructor_declaration", "constructor"),
+            ("compact_constructor_declaration", "constructor"),
+            ("field_declaration", "field"),
+        ],
+        references: &[("method_invocation", "name", "call"), ("object_creation_expression", "type", "new")],
+    },
+    LanguageIndex {
+        languages: &["go"],
+        package: Some("package_clause"),
+        definitions: &[("function_declaration", "function"), ("method_declaration", "method"), ("type_spec", "type")],
+        references: &[("call_expression", "function", "call")],
+    },
+    LanguageIndex {
+        languages: &["python"],
+        package: None,
+        definitions: &[("class_definition", "class"), ("function_definition", "function")],
+        references: &[("call", "function", "call")],
+    },
+    LanguageIndex {
+        languages: &["javascript", "jsx"],
+        package: None,
+        definitions: &[
+            ("class_declaration", "class"),
+            ("function_declaration", "function"),
+            ("generator_function_declaration", "function"),
+            ("method_definition", "method"),
+        ],
+        references: &[("call_expression", "function", "call"), ("new_expression", "constructor", "new")],
+    },
+    LanguageIndex {
+        languages: &["typescript", "tsx"],
+        package: None,
+        definitions: &[
+            ("class_declaration", "class"),
+            ("abstract_class_declaration", "class"),
+            ("interface_declaration", "interface"),
+            ("enum_declaration", "enum"),
+            ("type_alias_declaration", "type"),
+            ("function_declaration", "function"),
+            ("generator_function_declaration", "function"),
+            ("method_definition", "method"),
+            ("abstract_method_signature", "method"),
+        ],
+        references: &[("call_expression", "function", "call"), ("new_expression", "constructor", "new")],
+    },
+];
+
+/// A name declared in a file: a type, a function, a method or a field.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Definition {
+    pub name: String,
+    // name prefixed by the package and the enclosing definitions (e.g. com.example.Outer.Inner.run)
+    pub qualified_name: String,
+    pub kind: String,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    // index of the enclosing definition (type or function) in the definitions of the file
+    pub container: Option<usize>,
+}
+
+/// A use of a name in a file: a call or an object creation.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Reference {
+    pub name: String,
+    pub kind: String,
+    pub line: usize,
+    pub column: usize,
+    // index of the enclosing definition in the definitions of the file
+    pub container: Option<usize>,
+}
+
+/// The definitions and the references of a file, with the hash of its content.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileIndex {
+    pub path: String,
+    pub language: String,
+    // SHA-256 of the content, in hexadecimal
+    pub hash: String,
+    // size and modification time (nanoseconds since the epoch, 0 when unknown) of the file when indexed
+    pub size: u64,
+    pub modified: u64,
+    pub definitions: Vec<Definition>,
+    pub references: Vec<Reference>,
+}
+
+impl FileIndex {
+    /// Returns true if the file has the same size and modification time as when it was
+    /// indexed, its content is then assumed to be the same.
+    pub fn is_up_to_date(&self, metadata: &Metadata) -> bool {
+        let modified = get_modification_time(metadata);
+        modified != 0 && self.modified == modified && self.size == metadata.len()
+    }
+
+    /// Returns the name of the definition containing a definition or a reference.
+    pub fn get_container_name(&self, container: Option<usize>) -> Option<&str> {
+        container.and_then(|c| self.definitions.get(c)).map(|d| d.qualified_name.as_str())
+    }
+}
+
+/// The index of a project, saved on disk so that the definitions and references of
+/// the files can be looked up without parsing them again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CodeIndex {
+    pub files: Vec<FileIndex>,
+    // simple name of the definitions, with the index of their file and their index in the file
+    pub symbols: BTreeMap<String, Vec<(usize, usize)>>,
+}
+
+impl CodeIndex {
+    /// Build the index of files. The symbols are the only data computed from several files,
+    /// they are built again from the files on each run: the definitions of the changed and the
+    /// deleted files are replaced and no other data depends on the content of another file.
+    pub fn new(files: Vec<FileIndex>) -> CodeIndex {
+        let mut symbols: BTreeMap<String, Vec<(usize, usize)>> = BTreeMap::new();
+        for (f, file) in files.iter().enumerate() {
+            for (d, definition) in file.definitions.iter().enumerate() {
+                symbols.entry(definition.name.clone()).or_default().push((f, d));
+            }
+        }
+        CodeIndex { files, symbols }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), DataflowError> {
+        let filename = path.to_string_lossy().to_string();
+        let mut content = INDEX_MAGIC.to_vec();
+        content.extend(INDEX_VERSION.to_le_bytes());
+        rmp_serde::encode::write(&mut content, self).map_err(|e| DataflowError::InvalidIndex {
+            path: filename.clone(),
+            reason: e.to_string(),
+        })?;
+        // write next to the index and rename, a reader never sees a partial index
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, content).map_err(|source| DataflowError::Io { path: filename.clone(), source })?;
+        std::fs::rename(&temporary, path).map_err(|source| DataflowError::Io { path: filename, source })
+    }
+
+    pub fn load(path: &Path) -> Result<CodeIndex, DataflowError> {
+        let filename = path.to_string_lossy().to_string();
+        let content = std::fs::read(path).map_err(|source| DataflowError::Io { path: filename.clone(), source })?;
+        let invalid = |reason: String| DataflowError::InvalidIndex { path: filename.clone(), reason };
+        if content.len() < 8 || &content[..4] != INDEX_MAGIC {
+            return Err(invalid("not an index file".to_string()));
+        }
+        let version = u32::from_le_bytes([content[4], content[5], content[6], content[7]]);
+        if version != INDEX_VERSION {
+            return Err(invalid(format!("version {} instead of {}, the index must be rebuilt", version, INDEX_VERSION)));
+        }
+        rmp_serde::from_slice(&content[8..]).map_err(|e| invalid(e.to_string()))
+    }
+
+    /// Returns the definitions of a name, simple (`run`) or qualified (`com.example.Task.run`).
+    pub fn find_definitions(&self, name: &str) -> Vec<(&FileIndex, &Definition)> {
+        let simple_name = name.rsplit('.').next().unwrap_or(name);
+        self.symbols
+            .get(simple_name)
+            .map(|locations| locations.iter().map(|(f, d)| (&self.files[*f], &self.files[*f].definitions[*d])).filter(|(_, d)| d.name == name || d.qualified_name == name).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the references to a simple name.
+    pub fn find_references(&self, name: &str) -> Vec<(&FileIndex, &Reference)> {
+        let simple_name = name.rsplit('.').next().unwrap_or(name);
+        self.files.iter().flat_map(|f| f.references.iter().filter(|r| r.name == simple_name).map(move |r| (f, r))).collect()
+    }
+}
+
+/// Returns the modification time of a file in nanoseconds since the epoch, 0 when unknown.
+pub fn get_modification_time(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// Returns the SHA-256 of the content of a file, in hexadecimal.
+pub fn hash_content(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Returns the names declared by a node: its name fields or the names of its declarators
+/// (e.g. `int a, b;`).
+fn get_declared_names<'a>(node: tree_sitter::Node<'a>) -> Vec<tree_sitter::Node<'a>> {
+    let mut cursor = node.walk();
+    let names: Vec<tree_sitter::Node> = node.children_by_field_name("name", &mut cursor).collect();
+    if !names.is_empty() {
+        return names;
+    }
+    let mut cursor = node.walk();
+    let declarators: Vec<tree_sitter::Node> = node.children_by_field_name("declarator", &mut cursor).collect();
+    declarators.iter().filter_map(|d| d.child_by_field_name("name")).collect()
+}
+
+/// Returns the simple name used by a reference: the last part of a qualified or generic name.
+fn get_referenced_name(node: tree_sitter::Node, code: &str) -> String {
+    let name = ["field", "attribute", "name"].iter().find_map(|f| node.child_by_field_name(f));
+    match (name, node.grammar_name()) {
+        (Some(name), _) => get_code_for_node(name, code),
+        (None, "generic_type") => node.named_child(0).map(|n| get_referenced_name(n, code)).unwrap_or_default(),
+        (None, _) => get_code_for_node(node, code).rsplit('.').next().unwrap_or_default().to_string(),
+    }
+}
+
+fn walk<'a>(node: tree_sitter::Node<'a>, language: &LanguageIndex, code: &str, prefix: &str, container: Option<usize>, index: &mut FileIndex) {
+    let mut prefix = prefix.to_string();
+    let mut container = container;
+
+    if let Some((_, kind)) = language.definitions.iter().find(|(n, _)| *n == node.grammar_name()) {
+        let names = get_declared_names(node);
+        for name_node in &names {
+            let name_node = *name_node;
+            let name = get_code_for_node(name_node, code);
+            let qualified_name = match prefix.is_empty() {
+                true => name.clone(),
+                false => format!("{}.{}", prefix, name),
+            };
+            let position = name_node.start_position();
+            index.definitions.push(Definition {
+                name,
+                qualified_name,
+                kind: kind.to_string(),
+                line: position.row + 1,
+                column: position.column + 1,
+                end_line: node.end_position().row + 1,
+                container,
+            });
+        }
+        // a declaration of several names (e.g. `int a, b = f();`) does not contain its children
+        if let (1, Some(definition)) = (names.len(), index.definitions.last()) {
+            prefix = definition.qualified_name.clone();
+            container = Some(index.definitions.len() - 1);
+        }
+    }
+
+    if let Some((_, field, kind)) = language.references.iter().find(|(n, _, _)| *n == node.grammar_name()) {
+        if let Some(name_node) = node.child_by_field_name(field) {
+            let position = name_node.start_position();
+            index.references.push(Reference {
+                name: get_referenced_name(name_node, code),
+                kind: kind.to_string(),
+                line: position.row + 1,
+                column: position.column + 1,
+                container,
+            });
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        walk(child, language, code, &prefix, container, index);
+    }
+}
+
+/// Index the definitions and the references of a parsed file. The language is the name
+/// of a language in lower case (e.g. `java`), the files of other languages only have a hash.
+pub fn index_file(path: &str, language: &str, tree: &Tree, code: &str, metadata: &Metadata) -> FileIndex {
+    let mut index = FileIndex {
+        path: path.to_string(),
+        language: language.to_string(),
+        hash: hash_content(code.as_bytes()),
+        size: metadata.len(),
+        modified: get_modification_time(metadata),
+        definitions: vec![],
+        references: vec![],
+    };
+    let Some(language) = LANGUAGES.iter().find(|l| l.languages.contains(&language)) else {
+        return index;
+    };
+
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let package = language
+        .package
+        .and_then(|p| root.named_children(&mut cursor).find(|c| c.grammar_name() == p))
+        .and_then(|p| p.named_child(0))
+        .map(|n| get_code_for_node(n, code))
+        .unwrap_or_default();
+    walk(root, language, code, &package, None, &mut index);
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::testing::{parse_java, temp_dir};
+
+    const CODE: &str = r#"
+package com.example;
+
+class Task {
+    void run() {
+        new Worker().start();
+    }
+}
+"#;
+
+    fn index_code(directory: &Path) -> FileIndex {
+        let path = directory.join("Task.java");
+        std::fs::write(&path, CODE).unwrap();
+        let tree = parse_java(CODE);
+        index_file(&path.to_string_lossy(), "java", &tree, CODE, &std::fs::metadata(&path).unwrap())
+    }
+
+    #[test]
+    fn an_index_is_read_as_written() {
+        let directory = temp_dir("index-round-trip");
+        let index = CodeIndex::new(vec![index_code(&directory)]);
+        let path = directory.join(DEFAULT_INDEX_FILE);
+        index.save(&path).unwrap();
+        let loaded = CodeIndex::load(&path).unwrap();
+
+        let definitions = loaded.find_definitions("com.example.Task.run");
+        assert_eq!(definitions.len(), 1);
+        assert_eq!((definitions[0].1.line, definitions[0].1.kind.as_str()), (5, "method"));
+        let references = loaded.find_references("Worker");
+        assert_eq!(references.len(), 1);
+        assert_eq!(references[0].0.get_container_name(references[0].1.container), Some("com.example.Task.run"));
+        assert_eq!(loaded.files[0].hash, hash_content(CODE.as_bytes()));
+    }
+
+    #[test]
+    fn the_definitions_of_a_deleted_file_are_not_found() {
+        let directory = temp_dir("index-deleted");
+        let other = "package com.example;\n\nclass Worker { void start() {} }";
+        let other_path = directory.join("Worker.java");
+        std::fs::write(&other_path, other).unwrap();
+        let worker = index_file(&other_path.to_string_lossy(), "java", &parse_java(other), other, &std::fs::metadata(&other_path).unwrap());
+        let task = index_code(&directory);
+        assert_eq!(CodeIndex::new(vec![task.clone(), worker]).find_definitions("start").len(), 1);
+
+        let index = CodeIndex::new(vec![task]);
+        assert!(index.find_definitions("start").is_empty());
+        assert_eq!(index.find_definitions("run").len(), 1);
+    }
+
+    #[test]
+    fn a_file_that_is_not_an_index_is_rejected() {
+        let directory = temp_dir("index-invalid");
+        let path = directory.join(DEFAULT_INDEX_FILE);
+        std::fs::write(&path, "class Task {}").unwrap();
+
+        assert!(matches!(CodeIndex::load(&path), Err(DataflowError::InvalidIndex { .. })));
+    }
+
+    #[test]
+    fn an_index_of_another_version_is_rejected() {
+        let directory = temp_dir("index-version");
+        let path = directory.join(DEFAULT_INDEX_FILE);
+        CodeIndex::new(vec![]).save(&path).unwrap();
+        let mut content = std::fs::read(&path).unwrap();
+        content[4..8].copy_from_slice(&(INDEX_VERSION + 1).to_le_bytes());
+        std::fs::write(&path, content).unwrap();
+
+        assert!(matches!(CodeIndex::load(&path), Err(DataflowError::InvalidIndex { .. })));
+    }
+
+    #[test]
+    fn a_file_is_up_to_date_with_the_same_size_and_modification_time() {
+        let directory = temp_dir("index-up-to-date");
+        let index = index_code(&directory);
+        let path = directory.join("Task.java");
+
+        assert!(index.is_up_to_date(&std::fs::metadata(&path).unwrap()));
+        std::fs::write(&path, format!("{}\n", CODE)).unwrap();
+        assert!(!index.is_up_to_date(&std::fs::metadata(&path).unwrap()));
+    }
+}
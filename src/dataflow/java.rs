@@ -1,27 +1,291 @@
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
-use tree_sitter;
+use tracing::{debug, debug_span, info_span, trace, warn};
 use tree_sitter::Tree;
 
-use crate::dataflow::common::{get_code_for_node, get_nodes_of_type};
-use crate::dataflow::model::{Container, ContainerKind, DataFlow, Node, NodeKind};
+use crate::dataflow::collections::{get_constant_key_name, get_element_access, get_elements_name, get_keys_name, is_collection_type, is_constant_key_name, is_map_type, ElementAccess};
+use crate::dataflow::common::{get_code_for_node, get_nodes_of_type, get_simple_type_name};
+use crate::dataflow::hierarchy::TypeHierarchy;
+use crate::dataflow::library::{is_primitive_type, Endpoint, Library, MethodSummary};
+use crate::dataflow::model::{CallKind, Container, ContainerKind, DataFlow, Invocation, Node, NodeKind};
+
+/// All the tree-sitter nodes that declare a type and produce a container.
+const TYPE_DECLARATIONS: [&str; 5] = [
+    "class_declaration",
+    "interface_declaration",
+    "enum_declaration",
+    "record_declaration",
+    "annotation_type_declaration",
+];
+
+/// The tree-sitter nodes whose return statements return from a different function.
+const FUNCTION_DECLARATIONS: [&str; 4] = [
+    "method_declaration",
+    "constructor_declaration",
+    "compact_constructor_declaration",
+    "lambda_expression",
+];
+
+/// The nodes (fields) of an enclosing type.
+struct Scope<'a> {
+    type_name: Option<String>,
+    qualified_name: Option<String>,
+    binary_name: Option<String>,
+    // fully qualified name of the superclass
+    superclass: Option<String>,
+    nodes: HashMap<String, Arc<Node<'a>>>,
+}
+
+/// The package and imports of a file, to get the fully qualified names of the types.
+#[derive(Default)]
+struct Imports {
+    package: Option<String>,
+    // fully qualified names of the imported types by simple name
+    types: HashMap<String, String>,
+    // packages imported with a wildcard
+    packages: Vec<String>,
+}
 
-struct WalkContext<'a> {
+pub struct WalkContext<'a> {
     code: &'a str,
+    library: &'a Library,
+    // the types of all the files, to match the thrown exceptions with the catch clauses
+    hierarchy: Arc<TypeHierarchy>,
+    imports: Arc<Imports>,
+    // enclosing types, innermost last
+    scopes: Vec<Arc<Scope<'a>>>,
+}
+
+impl<'a> WalkContext<'a> {
+    /// Returns a new context where the nodes of the container (e.g. the fields of a class)
+    /// are visible from the nested containers.
+    fn with_scope(&self, container: &Container<'a>) -> WalkContext<'a> {
+        let mut scopes = self.scopes.clone();
+        scopes.push(Arc::new(Scope {
+            type_name: container.name.clone(),
+            qualified_name: container.qualified_name.clone(),
+            binary_name: container.binary_name.clone(),
+            superclass: container.superclass.clone(),
+            nodes: container.nodes_by_name.clone(),
+        }));
+        WalkContext {
+            code: self.code,
+            library: self.library,
+            hierarchy: self.hierarchy.clone(),
+            imports: self.imports.clone(),
+            scopes,
+        }
+    }
+
+    /// Returns the qualified and binary names of a type declared in the
+    /// innermost enclosing type (e.g. Outer.Inner and Outer$Inner).
+    fn nested_type_names(&self, name: &String) -> (String, String) {
+        match self.scopes.last() {
+            Some(scope) => (
+                format!("{}.{}", scope.qualified_name.clone().unwrap_or_default(), name),
+                format!("{}${}", scope.binary_name.clone().unwrap_or_default(), name),
+            ),
+            None => (name.clone(), name.clone()),
+        }
+    }
+}
+
+/// Returns the fully qualified name of a type from its name in the code, using
+/// the imports of the file. A type of `java.lang` or of a package imported with
+/// a wildcard is found only when known by the library.
+fn qualify_type_name(type_name: &str, context: &WalkContext) -> String {
+    let without_arguments = type_name.split('<').next().unwrap_or(type_name).trim();
+    let (name, dimensions) = match without_arguments.find('[') {
+        Some(index) => without_arguments.split_at(index),
+        None => (without_arguments, ""),
+    };
+
+    if name.contains('.') || is_primitive_type(name) {
+        return without_arguments.to_string();
+    }
+
+    if let Some(qualified_name) = context.imports.types.get(name) {
+        return format!("{}{}", qualified_name, dimensions);
+    }
+
+    let packages = std::iter::once(&"java.lang".to_string()).chain(context.imports.packages.iter()).map(|p| format!("{}.{}", p, name)).collect::<Vec<String>>();
+    if let Some(qualified_name) = packages.into_iter().find(|q| context.library.has_type(q)) {
+        return format!("{}{}", qualified_name, dimensions);
+    }
+
+    match &context.imports.package {
+        Some(package) => format!("{}.{}{}", package, name, dimensions),
+        None => without_arguments.to_string(),
+    }
 }
 
+/// Returns the fully qualified name of the type of an expression, when it can be known
+/// without analyzing other methods. An identifier that is not a variable is a type,
+/// e.g. for the invocation of a static method.
+fn get_expression_type<'a>(node: tree_sitter::Node, container: &Container<'a>, context: &WalkContext<'a>) -> Option<String> {
+    match node.grammar_name() {
+        "identifier" => {
+            let name = get_code_for_node(node, context.code);
+            match resolve(&name, container, context) {
+                Some(variable) => variable.type_name.clone(),
+                None if name.starts_with(char::is_uppercase) => Some(qualify_type_name(&name, context)),
+                None => None,
+            }
+        }
+        "field_access" if is_this_access(node) => {
+            let name = get_code_for_node(node, context.code);
+            resolve(&name, container, context).and_then(|variable| variable.type_name.clone())
+        }
+        // a fully qualified type, e.g. java.net.URLDecoder or org.owasp.benchmark.helpers.DatabaseHelper
+        "field_access" | "scoped_identifier" => {
+            let name = get_code_for_node(node, context.code);
+            let segments: Vec<&str> = name.split('.').collect();
+            let (type_segment, package_segments) = segments.split_last()?;
+            let is_qualified_type = type_segment.starts_with(char::is_uppercase) && package_segments.iter().all(|s| s.starts_with(char::is_lowercase));
+            (context.library.has_type(&name) || is_qualified_type).then_some(name)
+        }
+        "method_invocation" => get_library_summary(node, container, context).and_then(|summary| summary.returns.clone()),
+        "object_creation_expression" | "cast_expression" => node.child_by_field_name("type").map(|t| qualify_type_name(&get_code_for_node(t, context.code), context)),
+        "string_literal" => Some("java.lang.String".to_string()),
+        "decimal_integer_literal" | "hex_integer_literal" | "octal_integer_literal" | "binary_integer_literal" => {
+            let long = get_code_for_node(node, context.code).ends_with(['l', 'L']);
+            Some(if long { "long" } else { "int" }.to_string())
+        }
+        "decimal_floating_point_literal" | "hex_floating_point_literal" => {
+            let float = get_code_for_node(node, context.code).ends_with(['f', 'F']);
+            Some(if float { "float" } else { "double" }.to_string())
+        }
+        "character_literal" => Some("char".to_string()),
+        "true" | "false" => Some("boolean".to_string()),
+        // new char[n] or new String[][] {...}
+        "array_creation_expression" => {
+            let mut cursor = node.walk();
+            let dimensions: usize = node.children_by_field_name("dimensions", &mut cursor).map(|d| get_code_for_node(d, context.code).matches('[').count()).sum();
+            let type_name = qualify_type_name(&get_code_for_node(node.child_by_field_name("type")?, context.code), context);
+            Some(format!("{}{}", type_name, "[]".repeat(dimensions)))
+        }
+        "parenthesized_expression" => node.named_child(0).and_then(|n| get_expression_type(n, container, context)),
+        _ => None,
+    }
+}
+
+/// Returns the fully qualified name of a type declared in the file from its qualified name
+/// in the file (e.g. Outer.Inner).
+fn get_full_type_name(qualified_name: &str, context: &WalkContext) -> String {
+    match &context.imports.package {
+        Some(package) => format!("{}.{}", package, qualified_name),
+        None => qualified_name.to_string(),
+    }
+}
+
+/// Returns the fully qualified name of the innermost enclosing type.
+fn get_current_type_name(context: &WalkContext) -> Option<String> {
+    let qualified_name = context.scopes.last()?.qualified_name.clone()?;
+    Some(get_full_type_name(&qualified_name, context))
+}
+
+/// Returns the arguments of a method invocation or an object creation.
+fn get_arguments(node: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+    let mut cursor = node.walk();
+    node.child_by_field_name("arguments")
+        .map(|a| a.named_children(&mut cursor).collect())
+        .unwrap_or_default()
+}
+
+/// Returns the method invoked by a method invocation, an object creation or an explicit
+/// constructor invocation: the type declaring it, when known, its name (`<init>` for a
+/// constructor) and how it is selected. A method invoked without a receiver or on `this`
+/// is a method of the enclosing type.
+fn get_invoked_method<'a>(node: tree_sitter::Node, container: &Container<'a>, context: &WalkContext<'a>) -> (Option<String>, String, CallKind) {
+    let superclass = || context.scopes.last().and_then(|s| s.superclass.clone());
+
+    if node.grammar_name() == "object_creation_expression" {
+        let type_name = node.child_by_field_name("type").map(|t| qualify_type_name(&get_code_for_node(t, context.code), context));
+        return (type_name, "<init>".to_string(), CallKind::CONSTRUCTOR);
+    }
+
+    // this(...) or super(...)
+    if node.grammar_name() == "explicit_constructor_invocation" {
+        let type_name = match node.child_by_field_name("constructor").map(|c| c.grammar_name()) {
+            Some("this") => get_current_type_name(context),
+            _ => superclass(),
+        };
+        return (type_name, "<init>".to_string(), CallKind::CONSTRUCTOR);
+    }
+
+    let method_name = node.child_by_field_name("name").map(|n| get_code_for_node(n, context.code)).unwrap_or_default();
+    match node.child_by_field_name("object") {
+        None => (get_current_type_name(context), method_name, CallKind::VIRTUAL),
+        Some(object) if object.grammar_name() == "this" => (get_current_type_name(context), method_name, CallKind::VIRTUAL),
+        Some(object) if object.grammar_name() == "super" => (superclass(), method_name, CallKind::SUPER),
+        Some(object) => {
+            // a receiver that is not a value is a type
+            let is_type = match object.grammar_name() {
+                "identifier" => {
+                    let name = get_code_for_node(object, context.code);
+                    resolve(&name, container, context).is_none() && name.starts_with(char::is_uppercase)
+                }
+                "field_access" | "scoped_identifier" => !is_this_access(object) && get_expression_type(object, container, context).is_some(),
+                _ => false,
+            };
+            let kind = if is_type { CallKind::STATIC } else { CallKind::VIRTUAL };
+            (get_expression_type(object, container, context), method_name, kind)
+        }
+    }
+}
+
+/// Returns the summary of the method invoked by a method invocation or an object creation,
+/// either from the library or from the rules.
+fn get_library_summary<'a>(node: tree_sitter::Node, container: &Container<'a>, context: &WalkContext<'a>) -> Option<&'a MethodSummary> {
+    let (type_name, method_name, _) = get_invoked_method(node, container, context);
+    let argument_types: Vec<Option<String>> = get_arguments(node).iter().map(|a| get_expression_type(*a, container, context)).collect();
+    context.library.get_summary(&type_name?, &method_name, &argument_types)
+}
+
+/// Find a node by its name, first in the current container and then in the
+/// enclosing types, from the innermost to the outermost.
+///
+/// A field of the current instance can be designated with `this.f` and a field
+/// of the instance of an enclosing class with `Outer.this.f`.
+fn resolve<'a>(name: &String, container: &Container<'a>, context: &WalkContext<'a>) -> Option<Arc<Node<'a>>> {
+    if let Some(node) = container.nodes_by_name.get(name) {
+        return Some(node.clone());
+    }
+
+    if let Some(field) = name.strip_prefix("this.") {
+        return context.scopes.last().and_then(|scope| scope.nodes.get(field).cloned());
+    }
+
+    if let Some((type_name, field)) = name.split_once(".this.") {
+        return context
+            .scopes
+            .iter()
+            .rev()
+            .find(|scope| scope.type_name.as_deref() == Some(type_name) || scope.qualified_name.as_deref() == Some(type_name))
+            .and_then(|scope| scope.nodes.get(field).cloned());
+    }
+
+    context.scopes.iter().rev().find_map(|scope| scope.nodes.get(name).cloned())
+}
 
-fn add_flow(source: &String, dest: &String, container: &mut Container, dataflow: &mut DataFlow, context: &WalkContext) {
-    println!("attempt adding flow from {} to {} in container {}", source, dest, container.name.clone().unwrap_or("no name".to_string()));
-    let source_opt = container.nodes_by_name.get(source);
-    let dest_opt = container.nodes_by_name.get(dest);
+fn add_flow<'a>(source: &String, dest: &String, container: &mut Container<'a>, context: &WalkContext<'a>) {
+    let source_opt = resolve(source, container, context);
+    let dest_opt = resolve(dest, container, context);
+    trace!(source, destination = dest, resolved = source_opt.is_some() && dest_opt.is_some(), "adding flow");
 
     if source == dest {
         return;
     }
 
     if let (Some(source), Some(dest)) = (source_opt, dest_opt) {
+        // the same node may be designated by different names (e.g. `f` and `this.f`)
+        if Arc::ptr_eq(&source, &dest) {
+            return;
+        }
+        if source.outbound.read().unwrap().iter().any(|o| Arc::ptr_eq(o, &dest)) {
+            return;
+        }
         {
             source.outbound.write().unwrap().push(dest.clone());
         }
@@ -31,61 +295,129 @@ fn add_flow(source: &String, dest: &String, container: &mut Container, dataflow:
     }
 }
 
-/// Returns all the assignment we need to get when there is a function call
-/// on the right hand side of an assignment
-pub fn get_identifiers_from_assignment(node: tree_sitter::Node) -> Vec<tree_sitter::Node> {
-    let mut res: Vec<tree_sitter::Node> = vec![];
-    if node.grammar_name() == "identifier" {
-        res.push(node.clone());
+/// Returns the names of the constant-key nodes of a map (see [get_constant_key_name]).
+fn get_constant_key_names(map_name: &str, container: &Container) -> Vec<String> {
+    let mut names: Vec<String> = container.nodes_by_name.keys().filter(|n| is_constant_key_name(n, map_name)).cloned().collect();
+    names.sort();
+    names
+}
+
+/// Returns the name of a variable or a field when an expression designates it
+/// directly (e.g. a collection or the receiver of an invocation).
+fn get_variable_name(node: tree_sitter::Node, context: &WalkContext) -> Option<String> {
+    if node.grammar_name() == "identifier" || (node.grammar_name() == "field_access" && is_this_access(node)) {
+        return Some(get_code_for_node(node, context.code));
     }
-    if node.grammar_name() == "method_invocation" {
-        let object_opt = node.child_by_field_name("object");
-        let arguments_opt = node.child_by_field_name("arguments");
-        if let Some(object) = object_opt {
-            if object.grammar_name() == "identifier" {
-                res.push(object);
-            }
-        }
+    None
+}
 
-        if let Some(arguments) = arguments_opt {
-            let mut cursor = node.walk();
-            let args = arguments.children(&mut cursor);
-            for arg in args {
-                res.extend(get_identifiers_from_assignment(arg));
+/// Returns the names of the nodes whose values are read by an access to the elements of a collection.
+/// The collection itself is included: its elements may come from a value that was not modeled
+/// element by element (e.g. an array returned by a method).
+fn get_element_read_sources(collection: &str, access: &ElementAccess, key: Option<tree_sitter::Node>, container: &Container, context: &WalkContext) -> Vec<String> {
+    let mut res = vec![collection.to_string()];
 
+    let constant_key = key.filter(|k| k.grammar_name() == "string_literal").map(|k| get_code_for_node(k, context.code));
+
+    match access {
+        ElementAccess::MapKeys => res.push(get_keys_name(collection)),
+        // values of unrelated constant keys are not read
+        ElementAccess::MapRead if constant_key.is_some() => {
+            res.push(get_elements_name(collection));
+            res.push(get_constant_key_name(collection, &constant_key.unwrap()));
+        }
+        _ => {
+            res.push(get_elements_name(collection));
+            res.extend(get_constant_key_names(collection, container));
+            if *access == ElementAccess::MapEntries {
+                res.push(get_keys_name(collection));
             }
         }
     }
 
-    if node.grammar_name() == "object_creation_expression" {
-        let arguments_opt = node.child_by_field_name("arguments");
-        
-        if let Some(arguments) = arguments_opt {
-            let mut cursor = node.walk();
-            let args = arguments.children(&mut cursor);
-            for arg in args {
-                res.extend(get_identifiers_from_assignment(arg));
+    res
+}
 
+/// Walk an expression and returns the names of all the nodes whose values flow into its
+/// value, typically on the right hand side of an assignment. The nodes of the calls
+/// of the expression are created on the way.
+fn walk_expression<'a>(node: tree_sitter::Node<'a>, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) -> Vec<String> {
+    let mut res: Vec<String> = vec![];
+
+    match node.grammar_name() {
+        "identifier" => res.push(get_code_for_node(node, context.code)),
+        // this.f or Outer.this.f
+        "field_access" if is_this_access(node) => res.push(get_code_for_node(node, context.code)),
+        "method_invocation" => res.extend(walk_method_invocation(node, false, container, dataflow, context)),
+        "object_creation_expression" => res.extend(walk_call(node, false, container, dataflow, context)),
+        // reading an element of an array
+        "array_access" => {
+            if let Some(array) = node.child_by_field_name("array") {
+                match get_variable_name(array, context) {
+                    Some(array_name) => res.extend(get_element_read_sources(&array_name, &ElementAccess::Read, None, container, context)),
+                    None => res.extend(walk_expression(array, container, dataflow, context)),
+                }
+            }
+            if let Some(index) = node.child_by_field_name("index") {
+                walk_expression(index, container, dataflow, context);
+            }
+        }
+        "array_initializer" | "binary_expression" | "parenthesized_expression" => {
+            let mut cursor = node.walk();
+            let children: Vec<tree_sitter::Node> = node.named_children(&mut cursor).collect();
+            for child in children {
+                res.extend(walk_expression(child, container, dataflow, context));
+            }
+        }
+        "cast_expression" => {
+            if let Some(value) = node.child_by_field_name("value") {
+                res.extend(walk_expression(value, container, dataflow, context));
             }
         }
+        "ternary_expression" => {
+            if let Some(condition) = node.child_by_field_name("condition") {
+                walk_expression(condition, container, dataflow, context);
+            }
+            for field in ["consequence", "alternative"] {
+                if let Some(child) = node.child_by_field_name(field) {
+                    res.extend(walk_expression(child, container, dataflow, context));
+                }
+            }
+        }
+        // other expressions may contain calls, assignments or lambdas but their values do not come from variables
+        _ => walk_method_declaration_content(node, container, dataflow, context),
     }
 
-    if node.grammar_name() == "binary_expression" {
-        let left_opt = node.child_by_field_name("left");
-        let right_opt = node.child_by_field_name("right");
+    res
+}
 
-        if let Some(left) = left_opt {
-            res.extend(get_identifiers_from_assignment(left));
-        }
-        if let Some(right) = right_opt {
-            res.extend(get_identifiers_from_assignment(right));
+/// Returns true if the node accesses a field of the current instance (`this.f`)
+/// or of the instance of an enclosing class (`Outer.this.f`).
+fn is_this_access(node: tree_sitter::Node) -> bool {
+    match node.child_by_field_name("object") {
+        Some(object) if object.grammar_name() == "this" => true,
+        Some(object) if object.grammar_name() == "field_access" => {
+            object.child_by_field_name("field").is_some_and(|field| field.grammar_name() == "this")
         }
+        _ => false,
     }
+}
 
-    return res;
+/// Returns the name of the variable or field being assigned. A field accessed
+/// through `this` keeps its qualifier (e.g. `this.f` or `Outer.this.f`), see [resolve].
+fn get_assigned_name(left: tree_sitter::Node, context: &WalkContext) -> Option<String> {
+    if left.grammar_name() == "identifier" {
+        return Some(get_code_for_node(left, context.code));
+    }
+
+    if left.grammar_name() == "field_access" && is_this_access(left) {
+        return Some(get_code_for_node(left, context.code));
+    }
+
+    None
 }
 
-fn walk_assignment_expression<'a, 'b>(node: tree_sitter::Node<'a>, container: &'b mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext) {
+fn walk_assignment_expression<'a>(node: tree_sitter::Node<'a>, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
     let left_opt = node.child_by_field_name("left");
     let right_opt = node.child_by_field_name("right");
 
@@ -94,75 +426,360 @@ fn walk_assignment_expression<'a, 'b>(node: tree_sitter::Node<'a>, container: &'
     }
 
     let left = left_opt.unwrap();
-    if left.grammar_name() == "identifier" {
-        let left_identifier = get_code_for_node(left, context.code);
 
-        let mut variable_node = Arc::new(Node {
-            name: Some(left_identifier.clone()),
-            kind: NodeKind::VARIABLE,
-            inbound: RwLock::new(vec![]),
-            outbound: RwLock::new(vec![]),
-            ts_node: Arc::new(left),
-        });
+    // writing an element of an array
+    if left.grammar_name() == "array_access" {
+        if let Some(array_name) = left.child_by_field_name("array").and_then(|a| get_variable_name(a, context)) {
+            let elements_name = get_element_node(&array_name, get_elements_name(&array_name), left, container, context);
+            for source in walk_expression(right_opt.unwrap(), container, dataflow, context) {
+                add_flow(&source, &elements_name, container, context);
+            }
+        }
+        return;
+    }
 
-        if !container.nodes_by_name.contains_key(&left_identifier) {
-            container.nodes.push(variable_node.clone());
-            container.nodes_by_name.insert(left_identifier.clone(), variable_node.clone());
+    if let Some(left_identifier) = get_assigned_name(left, context) {
+        // assigning a field of an enclosing type does not declare a new variable
+        if resolve(&left_identifier, container, context).is_none() {
+            create_node(left, &left_identifier, NodeKind::VARIABLE, None, container);
         }
 
-        let right_identifiers = get_identifiers_from_assignment(right_opt.unwrap());
+        let right_identifiers = walk_expression(right_opt.unwrap(), container, dataflow, context);
 
-        for right_identifier in right_identifiers {
-            let right_identifier_value = get_code_for_node(right_identifier, context.code);
-            add_flow(&right_identifier_value, &left_identifier, container, dataflow, context);
+        for right_identifier_value in right_identifiers {
+            add_flow(&right_identifier_value, &left_identifier, container, context);
         }
     }
 }
 
-fn walk_local_variable_declaration<'a, 'b>(node: tree_sitter::Node<'a>, container: &'b mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext) {
-    let declarator_opt = node.child_by_field_name("declarator");
-
-    if declarator_opt.is_none() {
+/// Create a new node in the container unless a node with the same name already exists.
+fn create_node<'a>(ts_node: tree_sitter::Node<'a>, name: &String, kind: NodeKind, type_name: Option<String>, container: &mut Container<'a>) {
+    if container.nodes_by_name.contains_key(name) {
         return;
     }
 
-    let declarator = declarator_opt.unwrap();
+    add_node(Node::new(name.clone(), kind, type_name, ts_node), container);
+}
 
-    let left_opt = declarator.child_by_field_name("name");
+fn add_node<'a>(node: Node<'a>, container: &mut Container<'a>) {
+    let node = Arc::new(node);
+    let name = node.name.clone().unwrap_or_default();
+    container.nodes.push(node.clone());
+    container.nodes_by_name.insert(name, node);
+}
+
+/// Returns the fully qualified type of a declaration (parameter, resource, local variable
+/// or field). For a variable declarator, the type is the one of the enclosing declaration.
+fn get_declared_type(node: tree_sitter::Node, context: &WalkContext) -> Option<String> {
+    let declaration = if node.grammar_name() == "variable_declarator" { node.parent()? } else { node };
+    let type_name = declaration.child_by_field_name("type").map(|t| get_code_for_node(t, context.code))?;
+
+    // the type of a local variable declared with var is not known without its value
+    if type_name == "var" {
+        return None;
+    }
+
+    // String... args is an array
+    if declaration.grammar_name() == "spread_parameter" {
+        return Some(format!("{}[]", qualify_type_name(&type_name, context)));
+    }
+    Some(qualify_type_name(&type_name, context))
+}
+
+/// Returns the name of the node holding elements of a collection, creating it if needed.
+/// The node of a local collection is created in the current container. The nodes of
+/// a field are created with the field (see [create_element_nodes]): values of a field
+/// stored with a constant key are merged with the other values and when the field is
+/// not known as a collection, its elements are the field itself.
+fn get_element_node<'a>(collection: &String, element_name: String, ts_node: tree_sitter::Node<'a>, container: &mut Container<'a>, context: &WalkContext<'a>) -> String {
+    if container.nodes_by_name.contains_key(collection) || resolve(collection, container, context).is_none() {
+        create_node(ts_node, &element_name, NodeKind::ELEMENT, None, container);
+        return element_name;
+    }
+
+    if resolve(&element_name, container, context).is_some() {
+        return element_name;
+    }
+
+    let elements_name = get_elements_name(collection);
+    if resolve(&elements_name, container, context).is_some() {
+        return elements_name;
+    }
+
+    collection.clone()
+}
+
+/// Create the nodes of the elements of a field of a collection type, so that
+/// they are shared by all the members of the type.
+fn create_element_nodes<'a>(ts_node: tree_sitter::Node<'a>, name: &str, type_name: &Option<String>, container: &mut Container<'a>) {
+    if let Some(type_name) = type_name {
+        if is_collection_type(type_name) {
+            create_node(ts_node, &get_elements_name(name), NodeKind::ELEMENT, None, container);
+        }
+        if is_map_type(type_name) {
+            create_node(ts_node, &get_keys_name(name), NodeKind::ELEMENT, None, container);
+        }
+    }
+}
 
+/// Create the node for a variable declarator (local variable or field) and add
+/// the flows from its initial value.
+fn walk_variable_declarator<'a>(declarator: tree_sitter::Node<'a>, kind: NodeKind, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
+    let left_opt = declarator.child_by_field_name("name");
     let right_opt = declarator.child_by_field_name("value");
 
-    if left_opt.is_none() || right_opt.is_none() {
+    if let Some(left) = left_opt {
+        if left.grammar_name() != "identifier" {
+            return;
+        }
+        let left_identifier = get_code_for_node(left, context.code);
+        let type_name = get_declared_type(declarator, context);
+        if matches!(kind, NodeKind::FIELD) {
+            create_element_nodes(left, &left_identifier, &type_name, container);
+        }
+        create_node(left, &left_identifier, kind, type_name, container);
+
+        if let Some(right) = right_opt {
+            let right_identifiers = walk_expression(right, container, dataflow, context);
+
+            for right_identifier_value in right_identifiers {
+                add_flow(&right_identifier_value, &left_identifier, container, context);
+            }
+        }
+    }
+}
+
+/// Add the flows from the arguments of a method storing them in a collection or a map
+/// to the nodes of the elements (or keys and values) of the collection.
+fn walk_element_write<'a>(node: tree_sitter::Node<'a>, collection: &String, access: ElementAccess, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
+    let arguments = get_arguments(node);
+
+    let mut sources = vec![];
+    let mut key_sources = vec![];
+    let mut elements_name = get_elements_name(collection);
+
+    match access {
+        ElementAccess::Write => {
+            for argument in &arguments {
+                sources.extend(walk_expression(*argument, container, dataflow, context));
+            }
+        }
+        ElementAccess::WriteAll | ElementAccess::MapWriteAll => {
+            for argument in &arguments {
+                sources.extend(walk_expression(*argument, container, dataflow, context));
+                if let Some(other) = get_variable_name(*argument, context) {
+                    sources.push(get_elements_name(&other));
+                    sources.extend(get_constant_key_names(&other, container));
+                    key_sources.push(get_keys_name(&other));
+                }
+            }
+        }
+        ElementAccess::MapWrite => {
+            if let Some(key) = arguments.first() {
+                key_sources.extend(walk_expression(*key, container, dataflow, context));
+                if key.grammar_name() == "string_literal" {
+                    elements_name = get_constant_key_name(collection, &get_code_for_node(*key, context.code));
+                }
+            }
+            for argument in arguments.iter().skip(1) {
+                sources.extend(walk_expression(*argument, container, dataflow, context));
+            }
+        }
+        _ => return,
+    }
+
+    if !sources.is_empty() {
+        let elements_node_name = get_element_node(collection, elements_name, node, container, context);
+        for source in sources {
+            add_flow(&source, &elements_node_name, container, context);
+        }
+    }
+
+    if !key_sources.is_empty() && matches!(access, ElementAccess::MapWrite | ElementAccess::MapWriteAll) {
+        let keys_node_name = get_element_node(collection, get_keys_name(collection), node, container, context);
+        for source in key_sources {
+            add_flow(&source, &keys_node_name, container, context);
+        }
+    }
+}
+
+/// Walk a method invocation and returns the names of the nodes whose values flow into its
+/// returned value. An invocation accessing the elements of a collection reads or writes
+/// the nodes of the elements, any other invocation is a call (see [walk_call]).
+fn walk_method_invocation<'a>(node: tree_sitter::Node<'a>, statement: bool, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) -> Vec<String> {
+    let object_opt = node.child_by_field_name("object");
+    let method_name = node.child_by_field_name("name").map(|n| get_code_for_node(n, context.code)).unwrap_or_default();
+
+    // the receiver is a local variable or a field of a known type, not a type (e.g. Arrays.stream)
+    let collection = object_opt.and_then(|o| get_variable_name(o, context));
+    let collection_type = collection.as_ref().and_then(|c| resolve(c, container, context)).and_then(|n| n.type_name.clone());
+    if let (Some(collection), Some(collection_type)) = (collection, collection_type) {
+        match get_element_access(&method_name, &collection_type) {
+            Some(access) if matches!(access, ElementAccess::Write | ElementAccess::WriteAll | ElementAccess::MapWrite | ElementAccess::MapWriteAll) => {
+                walk_element_write(node, &collection, access, container, dataflow, context);
+                return vec![];
+            }
+            Some(access) => {
+                let arguments = get_arguments(node);
+                let mut res = get_element_read_sources(&collection, &access, arguments.first().copied(), container, context);
+                for (index, argument) in arguments.iter().enumerate() {
+                    let sources = walk_expression(*argument, container, dataflow, context);
+                    // the default value of getOrDefault
+                    if access == ElementAccess::MapRead && index > 0 {
+                        res.extend(sources);
+                    }
+                }
+                return res;
+            }
+            None => {}
+        }
+    }
+
+    walk_call(node, statement, container, dataflow, context)
+}
+
+/// Create a CALL or an ARGUMENT node in the container unless a node with the same name already exists.
+fn create_call_node<'a>(ts_node: tree_sitter::Node<'a>, name: &String, kind: NodeKind, type_name: Option<String>, invocation: Invocation, sanitized: bool, container: &mut Container<'a>) {
+    if container.nodes_by_name.contains_key(name) {
         return;
     }
 
-    let left = left_opt.unwrap();
-    if left.grammar_name() == "identifier" {
-        let left_identifier = get_code_for_node(left, context.code);
+    let mut node = Node::new(name.clone(), kind, type_name, ts_node);
+    node.invocation = Some(invocation);
+    node.sanitized = sanitized;
+    add_node(node, container);
+}
 
-        let mut variable_node = Arc::new(Node {
-            name: Some(left_identifier.clone()),
-            kind: NodeKind::VARIABLE,
-            inbound: RwLock::new(vec![]),
-            outbound: RwLock::new(vec![]),
-            ts_node: Arc::new(left),
-        });
+/// Walk a call (a method invocation or an object creation): it gets a CALL node for its
+/// returned value and an ARGUMENT node for each of its arguments. The values flow between
+/// the receiver, the arguments and the returned value according to the summary of the
+/// method. Without a summary, the receiver and the arguments flow into the returned
+/// value and, for a call used as a statement, the arguments flow into the receiver.
+/// Returns the name of the CALL node.
+fn walk_call<'a>(node: tree_sitter::Node<'a>, statement: bool, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) -> Vec<String> {
+    let (type_name, method_name, kind) = get_invoked_method(node, container, context);
+    let arguments = get_arguments(node);
+    let summary = get_library_summary(node, container, context);
+    let sanitizes = |endpoint: &Endpoint| summary.is_some_and(|s| s.sanitizes(endpoint));
+
+    // calls are named after the method and the position of its name, unique in the function
+    let call_name = if node.grammar_name() == "object_creation_expression" {
+        let type_node = node.child_by_field_name("type").unwrap_or(node);
+        let position = type_node.start_position();
+        format!("new {}()@{}:{}", get_simple_type_name(&get_code_for_node(type_node, context.code)), position.row + 1, position.column + 1)
+    } else if node.grammar_name() == "explicit_constructor_invocation" {
+        let constructor = node.child_by_field_name("constructor").unwrap_or(node);
+        let position = constructor.start_position();
+        format!("{}()@{}:{}", get_code_for_node(constructor, context.code), position.row + 1, position.column + 1)
+    } else {
+        let position = node.child_by_field_name("name").unwrap_or(node).start_position();
+        format!("{}()@{}:{}", method_name, position.row + 1, position.column + 1)
+    };
+    let returned_type = match node.grammar_name() {
+        "object_creation_expression" => type_name.clone(),
+        _ => summary.and_then(|s| s.returns.clone()),
+    };
+    let invocation = Invocation {
+        kind,
+        type_name,
+        method: method_name,
+        arguments: arguments.len(),
+        argument_types: arguments.iter().map(|a| get_expression_type(*a, container, context)).collect(),
+        argument: None,
+    };
+    create_call_node(node, &call_name, NodeKind::CALL, returned_type, invocation.clone(), sanitizes(&Endpoint::Return), container);
+
+    // a collection created from another one gets its elements
+    let copies_elements = node.grammar_name() == "object_creation_expression"
+        && node.child_by_field_name("type").is_some_and(|t| is_collection_type(&get_code_for_node(t, context.code)));
+
+    let mut argument_names = vec![];
+    for (index, argument) in arguments.iter().enumerate() {
+        let argument_name = format!("{}#{}", call_name, index);
+        let argument_invocation = Invocation {
+            argument: Some(index),
+            ..invocation.clone()
+        };
+        create_call_node(*argument, &argument_name, NodeKind::ARGUMENT, None, argument_invocation, sanitizes(&Endpoint::Argument(index)), container);
+
+        let mut sources = walk_expression(*argument, container, dataflow, context);
+        if copies_elements {
+            if let Some(collection) = get_variable_name(*argument, context) {
+                sources.extend(get_element_read_sources(&collection, &ElementAccess::MapEntries, None, container, context));
+            }
+        }
+        for source in sources {
+            add_flow(&source, &argument_name, container, context);
+        }
+        argument_names.push(argument_name);
+    }
+
+    let receiver_opt = node.child_by_field_name("object").filter(|o| o.grammar_name() != "this" && o.grammar_name() != "super");
+    let receiver_sources = receiver_opt.map(|r| walk_expression(r, container, dataflow, context)).unwrap_or_default();
+    let receiver_variable = receiver_opt.and_then(|r| get_variable_name(r, context));
+
+    let flows: Vec<(Endpoint, Endpoint)> = match summary {
+        Some(summary) => summary.flows.iter().map(|f| (f.from.clone(), f.to.clone())).collect(),
+        None if statement => vec![(Endpoint::Receiver, Endpoint::Return), (Endpoint::Arguments, Endpoint::Return), (Endpoint::Arguments, Endpoint::Receiver)],
+        None => vec![(Endpoint::Receiver, Endpoint::Return), (Endpoint::Arguments, Endpoint::Return)],
+    };
 
-        if !container.nodes_by_name.contains_key(&left_identifier) {
-            container.nodes.push(variable_node.clone());
-            container.nodes_by_name.insert(left_identifier.clone(), variable_node.clone());
+    for (from, to) in flows {
+        let sources = match from {
+            Endpoint::Receiver => receiver_sources.clone(),
+            Endpoint::Return => vec![call_name.clone()],
+            Endpoint::Argument(index) => argument_names.get(index).cloned().into_iter().collect(),
+            Endpoint::Arguments => argument_names.clone(),
+        };
+        // values flowing to an argument modify the variable given as argument
+        let destinations: Vec<String> = match to {
+            Endpoint::Receiver => receiver_variable.clone().into_iter().collect(),
+            Endpoint::Return => vec![call_name.clone()],
+            Endpoint::Argument(index) => arguments.get(index).and_then(|a| get_variable_name(*a, context)).into_iter().collect(),
+            Endpoint::Arguments => arguments.iter().filter_map(|a| get_variable_name(*a, context)).collect(),
+        };
+        for source in &sources {
+            for destination in &destinations {
+                add_flow(source, destination, container, context);
+            }
         }
+    }
+
+    vec![call_name]
+}
 
-        let right_identifiers = get_identifiers_from_assignment(right_opt.unwrap());
+/// The variable of an enhanced for statement gets the elements of the collection.
+fn walk_enhanced_for_statement<'a>(node: tree_sitter::Node<'a>, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
+    let name_opt = node.child_by_field_name("name");
+    let value_opt = node.child_by_field_name("value");
+
+    if let (Some(name), Some(value)) = (name_opt, value_opt) {
+        let variable_name = get_code_for_node(name, context.code);
+        let type_name = get_declared_type(node, context);
+        create_node(name, &variable_name, NodeKind::VARIABLE, type_name, container);
+
+        let mut sources = walk_expression(value, container, dataflow, context);
+        if let Some(collection) = get_variable_name(value, context) {
+            sources.extend(get_element_read_sources(&collection, &ElementAccess::Read, None, container, context));
+        }
+        for source in sources {
+            add_flow(&source, &variable_name, container, context);
+        }
+    }
+}
 
-        for right_identifier in right_identifiers {
-            let right_identifier_value = get_code_for_node(right_identifier, context.code);
-            add_flow(&right_identifier_value, &left_identifier, container, dataflow, context);
+/// Walk the declarators with an initial value, e.g. `a` and `b` in `String a = x, b = y, c;`.
+fn walk_local_variable_declaration<'a>(node: tree_sitter::Node<'a>, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
+    let mut cursor = node.walk();
+    let declarators: Vec<tree_sitter::Node> = node.children_by_field_name("declarator", &mut cursor).collect();
+    for declarator in declarators {
+        if declarator.child_by_field_name("value").is_some() {
+            walk_variable_declarator(declarator, NodeKind::VARIABLE, container, dataflow, context);
         }
     }
 }
 
-fn walk_method_declaration_content<'a, 'b>(node: tree_sitter::Node<'a>, container: &'b mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext) {
+fn walk_method_declaration_content<'a>(node: tree_sitter::Node<'a>, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
     if node.grammar_name() == "assignment_expression" {
         walk_assignment_expression(node, container, dataflow, context);
         return;
@@ -173,55 +790,256 @@ fn walk_method_declaration_content<'a, 'b>(node: tree_sitter::Node<'a>, containe
         return;
     }
 
-    if node.grammar_name() == "method_invocation" {
-        let object_opt = node.child_by_field_name("object");
-        let arguments_opt = node.child_by_field_name("arguments");
-        if let Some(object) = object_opt {
-            if object.grammar_name() == "identifier" {
-                let object_name = get_code_for_node(object, context.code);
+    // a resource of a try-with-resources statement is declared like a local variable
+    if node.grammar_name() == "resource" {
+        if node.child_by_field_name("name").is_some() {
+            walk_variable_declarator(node, NodeKind::VARIABLE, container, dataflow, context);
+        }
+        return;
+    }
 
+    if node.grammar_name() == "throw_statement" {
+        walk_throw_statement(node, container, dataflow, context);
+        return;
+    }
 
-                if let Some(arguments) = arguments_opt {
-                    let identifiers = get_nodes_of_type(arguments, "identifier");
-                    for id in identifiers {
-                        let arg_name = get_code_for_node(id, context.code);
-                        add_flow(&arg_name, &object_name, container, dataflow, context);
+    if node.grammar_name() == "return_statement" {
+        walk_return_statement(node, container, dataflow, context);
+        return;
+    }
 
-                    }
-                }
-            }
-        }
+    // the catch parameters must exist before walking the throw statements of the try block
+    if node.grammar_name() == "try_statement" || node.grammar_name() == "try_with_resources_statement" {
+        walk_catch_parameters(node, container, context);
+    }
 
+    // local classes are containers of the method
+    if TYPE_DECLARATIONS.contains(&node.grammar_name()) {
+        walk_type_declaration(node, container, dataflow, context);
         return;
     }
 
+    if node.grammar_name() == "method_invocation" {
+        walk_method_invocation(node, true, container, dataflow, context);
+        return;
+    }
+
+    if node.grammar_name() == "explicit_constructor_invocation" {
+        walk_call(node, true, container, dataflow, context);
+        return;
+    }
+
+    // the body of an anonymous class is walked with the method
+    if node.grammar_name() == "object_creation_expression" {
+        walk_call(node, true, container, dataflow, context);
+        let mut cursor = node.walk();
+        let body = node.named_children(&mut cursor).find(|c| c.grammar_name() == "class_body");
+        if let Some(body) = body {
+            walk_method_declaration_content(body, container, dataflow, context);
+        }
+        return;
+    }
+
+    if node.grammar_name() == "enhanced_for_statement" {
+        walk_enhanced_for_statement(node, container, dataflow, context);
+    }
+
     let mut cursor = node.walk();
     let children = node.children(&mut cursor);
     for child in children {
         if child.is_named() {
-            // println!("[walk_method_declaration] type: {}", child.grammar_name());
             walk_method_declaration_content(child, container, dataflow, context);
         }
     }
 }
 
-fn walk_parameter_declaration<'a, 'b>(node: tree_sitter::Node<'a>, method_container: &'b mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext) {
+/// Name of the node that receives the exceptions thrown out of a function.
+const THROWS_NODE_NAME: &str = "<throws>";
+
+/// Name of the node that receives the values returned by a function.
+pub const RETURN_NODE_NAME: &str = "<return>";
+
+/// The supertypes of the exceptions: all of them, the ones that are not errors and the unchecked ones.
+const THROWABLE_TYPE: &str = "java.lang.Throwable";
+const EXCEPTION_TYPE: &str = "java.lang.Exception";
+const RUNTIME_EXCEPTION_TYPE: &str = "java.lang.RuntimeException";
+
+/// Add the flows from the value of a return statement to the value returned by the function.
+/// The values returned by a lambda or by a method of an anonymous class are not returned
+/// by the enclosing function.
+fn walk_return_statement<'a>(node: tree_sitter::Node<'a>, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
+    let expression_opt = node.named_child(0);
+
+    if expression_opt.is_none() {
+        return;
+    }
+
+    let sources = walk_expression(expression_opt.unwrap(), container, dataflow, context);
+    let function = std::iter::successors(node.parent(), |n| n.parent()).find(|n| FUNCTION_DECLARATIONS.contains(&n.grammar_name()));
+    if function.is_none() || function != container.ts_node {
+        return;
+    }
+
+    create_node(node, &RETURN_NODE_NAME.to_string(), NodeKind::RETURN, None, container);
+    for source in &sources {
+        add_flow(source, &RETURN_NODE_NAME.to_string(), container, context);
+    }
+}
+
+/// Returns true if a catch clause catches an exception, both given by their fully qualified
+/// names. The supertypes of the exception are found in the analyzed code and in the library.
+/// An exception whose supertypes are unknown (e.g. from a dependency) may be any exception,
+/// checked or not, but not an error: it is caught by `Throwable`, `Exception` and `RuntimeException`.
+fn catches(catch_type: &str, thrown_type: &str, context: &WalkContext) -> bool {
+    let ancestors = context.hierarchy.get_ancestors(thrown_type);
+    let is_subtype = |supertype: &str| ancestors.iter().any(|a| a == supertype || context.library.is_subtype(a, supertype));
+    if is_subtype(catch_type) {
+        return true;
+    }
+    let is_known = ancestors.iter().any(|a| context.library.has_type(a));
+    !is_known && [THROWABLE_TYPE, EXCEPTION_TYPE, RUNTIME_EXCEPTION_TYPE].contains(&catch_type)
+}
+
+/// Create the nodes of the parameters of all the catch clauses of a try statement.
+fn walk_catch_parameters<'a>(node: tree_sitter::Node<'a>, container: &mut Container<'a>, context: &WalkContext<'a>) {
+    let mut cursor = node.walk();
+    for catch_clause in node.named_children(&mut cursor).filter(|c| c.grammar_name() == "catch_clause") {
+        let mut catch_cursor = catch_clause.walk();
+        for parameter in catch_clause.named_children(&mut catch_cursor).filter(|c| c.grammar_name() == "catch_formal_parameter") {
+            if let Some(name) = parameter.child_by_field_name("name") {
+                let parameter_name = get_code_for_node(name, context.code);
+                let type_name = get_declared_type(parameter, context);
+                create_node(name, &parameter_name, NodeKind::VARIABLE, type_name, container);
+            }
+        }
+    }
+}
+
+/// Returns true if a node is the body of a method, a constructor or an initializer block.
+fn is_function_body(node: tree_sitter::Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    match parent.grammar_name() {
+        "method_declaration" | "constructor_declaration" | "compact_constructor_declaration" => parent.child_by_field_name("body") == Some(node),
+        "static_initializer" => true,
+        // an instance initializer
+        "class_body" => node.grammar_name() == "block",
+        _ => false,
+    }
+}
+
+/// Returns the names of the catch parameters that may receive an exception thrown
+/// by a statement and if the exception may be thrown out of the function.
+/// When the type of the exception is unknown, it may be caught by any enclosing catch clause.
+fn get_exception_handlers(node: tree_sitter::Node, thrown_type: Option<&String>, context: &WalkContext) -> (Vec<String>, bool) {
+    let mut handlers = vec![];
+    let mut current = node;
+
+    while let Some(parent) = current.parent() {
+        match parent.grammar_name() {
+            // only the exceptions thrown in the try block are caught, not the ones from catch and finally
+            "try_statement" | "try_with_resources_statement" if parent.child_by_field_name("body") == Some(current) => {
+                let mut cursor = parent.walk();
+                for catch_clause in parent.named_children(&mut cursor).filter(|c| c.grammar_name() == "catch_clause") {
+                    let mut catch_cursor = catch_clause.walk();
+                    for parameter in catch_clause.named_children(&mut catch_cursor).filter(|c| c.grammar_name() == "catch_formal_parameter") {
+                        let name_opt = parameter.child_by_field_name("name").map(|n| get_code_for_node(n, context.code));
+                        let catch_types = get_nodes_of_type(parameter, "catch_type")
+                            .iter()
+                            .flat_map(|catch_type| {
+                                let mut type_cursor = catch_type.walk();
+                                catch_type
+                                    .named_children(&mut type_cursor)
+                                    .map(|t| qualify_type_name(&get_code_for_node(t, context.code), context))
+                                    .collect::<Vec<String>>()
+                            })
+                            .collect::<Vec<String>>();
+
+                        if let Some(name) = name_opt {
+                            match thrown_type {
+                                Some(thrown) if catch_types.iter().any(|c| catches(c, thrown, context)) => {
+                                    handlers.push(name);
+                                    return (handlers, false);
+                                }
+                                Some(_) => {}
+                                None => handlers.push(name),
+                            }
+                        }
+                    }
+                }
+            }
+            // the exception is thrown when the lambda is called, not by the function
+            "lambda_expression" => return (handlers, false),
+            _ if is_function_body(current) => return (handlers, true),
+            _ => {}
+        }
+        current = parent;
+    }
+
+    (handlers, true)
+}
+
+/// Add the flows from the value thrown by a throw statement to the parameters of
+/// the catch clauses that may catch it, or to the exceptions thrown by the function.
+fn walk_throw_statement<'a>(node: tree_sitter::Node<'a>, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
+    let expression_opt = node.named_child(0);
+
+    if expression_opt.is_none() {
+        return;
+    }
+
+    let expression = expression_opt.unwrap();
+    let thrown_type = get_expression_type(expression, container, context);
+
+    let sources = walk_expression(expression, container, dataflow, context);
+    let (handlers, escapes) = get_exception_handlers(node, thrown_type.as_ref(), context);
+
+    for handler in handlers {
+        for source in &sources {
+            add_flow(source, &handler, container, context);
+        }
+    }
+
+    // the exceptions thrown out of a method of an anonymous class are not thrown by the function
+    let in_anonymous_class = std::iter::successors(node.parent(), |n| n.parent())
+        .take_while(|n| Some(*n) != container.ts_node)
+        .any(|n| n.grammar_name() == "class_body" || n.grammar_name() == "object_creation_expression");
+    if escapes && !in_anonymous_class {
+        // the exceptions leave the function through its throws clause
+        let throws_clause = container.ts_node.and_then(|f| {
+            let mut cursor = f.walk();
+            let throws = f.named_children(&mut cursor).find(|c| c.grammar_name() == "throws");
+            throws
+        });
+        create_node(throws_clause.unwrap_or(node), &THROWS_NODE_NAME.to_string(), NodeKind::EXCEPTION, None, container);
+        for source in &sources {
+            add_flow(source, &THROWS_NODE_NAME.to_string(), container, context);
+        }
+    }
+}
+
+fn walk_parameter_declaration<'a>(node: tree_sitter::Node<'a>, method_container: &mut Container<'a>, context: &WalkContext<'a>) {
     let name_opt = node.child_by_field_name("name");
     if let Some(name) = name_opt {
         let parameter_name = get_code_for_node(name, context.code);
-        let mut param_node = Arc::new(Node {
-            name: Some(parameter_name.clone()),
-            kind: NodeKind::PARAMETER,
-            inbound: RwLock::new(vec![]),
-            outbound: RwLock::new(vec![]),
-            ts_node: Arc::new(node),
-        });
-        method_container.nodes.push(param_node.clone());
-        method_container.nodes_by_name.insert(parameter_name.clone(), param_node.clone());
+        add_node(Node::new(parameter_name, NodeKind::PARAMETER, get_declared_type(node, context), node), method_container);
+    }
+}
+
+fn walk_parameters<'a>(parameters: tree_sitter::Node<'a>, container: &mut Container<'a>, context: &WalkContext<'a>) {
+    let mut cursor = parameters.walk();
+    let children = parameters.children(&mut cursor);
+    for child in children {
+        if child.is_named() && (child.grammar_name() == "formal_parameter" || child.grammar_name() == "spread_parameter") {
+            walk_parameter_declaration(child, container, context);
+        }
     }
 }
 
-fn walk_method_declaration<'a, 'b>(node: tree_sitter::Node<'a>, class_container: &'b mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext) {
+/// Walk a method, a constructor or an annotation element. The kind of
+/// container is given by the caller.
+fn walk_method_declaration<'a>(node: tree_sitter::Node<'a>, kind: ContainerKind, class_container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
     let method_name_opt = node
         .child_by_field_name("name")
         .map(|n| get_code_for_node(n, context.code));
@@ -230,26 +1048,21 @@ fn walk_method_declaration<'a, 'b>(node: tree_sitter::Node<'a>, class_container:
         return;
     }
 
-    let mut container = Container {
-        name: method_name_opt,
-        kind: ContainerKind::FUNCTION,
-        containers: vec![],
-        nodes: vec![],
-        nodes_by_name: HashMap::new(),
-    };
+    let _span = debug_span!("container", name = method_name_opt.as_deref(), kind = ?kind).entered();
+    let mut container = Container::new(method_name_opt, kind);
+    container.ts_node = Some(node);
 
 
     let parameters_opt = node.child_by_field_name("parameters");
 
     if let Some(parameters) = parameters_opt {
-        let mut cursor = parameters.walk();
-        let children = parameters.children(&mut cursor);
-        for child in children {
-            if child.is_named() {
-                if child.grammar_name() == "formal_parameter" {
-                    walk_parameter_declaration(child, &mut container, dataflow, context);
-                }
-            }
+        walk_parameters(parameters, &mut container, context);
+    }
+
+    // the parameters of a compact constructor are the components of the record
+    if node.grammar_name() == "compact_constructor_declaration" {
+        if let Some(record_parameters) = node.parent().and_then(|b| b.parent()).and_then(|r| r.child_by_field_name("parameters")) {
+            walk_parameters(record_parameters, &mut container, context);
         }
     }
 
@@ -262,85 +1075,777 @@ fn walk_method_declaration<'a, 'b>(node: tree_sitter::Node<'a>, class_container:
         }
     }
 
+    // at the end of a compact constructor, the parameters are assigned to the fields
+    if node.grammar_name() == "compact_constructor_declaration" {
+        let parameters: Vec<String> = container.nodes.iter().filter(|n| matches!(n.kind, NodeKind::PARAMETER)).filter_map(|n| n.name.clone()).collect();
+        for parameter in parameters {
+            add_flow(&parameter, &format!("this.{}", parameter), &mut container, context);
+        }
+    }
 
     class_container.containers.push(Arc::new(container));
+}
 
+/// Walk a static or an instance initializer block.
+fn walk_initializer<'a>(node: tree_sitter::Node<'a>, name: &str, class_container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
+    let _span = debug_span!("container", name, kind = ?ContainerKind::INITIALIZER).entered();
+    let mut container = Container::new(Some(name.to_string()), ContainerKind::INITIALIZER);
+    container.ts_node = Some(node);
 
-    // walk_method_declaration_content(&node, context);
+    walk_method_declaration_content(node, &mut container, dataflow, context);
+
+    class_container.containers.push(Arc::new(container));
+}
+
+/// Create the nodes for the fields declared in the body of a type: field and
+/// constant declarations and enum constants.
+fn walk_fields<'a>(node: tree_sitter::Node<'a>, class_container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
+    let mut cursor = node.walk();
+    let children = node.children(&mut cursor);
+    for child in children {
+        match child.grammar_name() {
+            "field_declaration" | "constant_declaration" => {
+                let mut declarator_cursor = child.walk();
+                for declarator in child.children_by_field_name("declarator", &mut declarator_cursor) {
+                    walk_variable_declarator(declarator, NodeKind::FIELD, class_container, dataflow, context);
+                }
+            }
+            "enum_constant" => {
+                if let Some(name) = child.child_by_field_name("name") {
+                    let constant_name = get_code_for_node(name, context.code);
+                    let type_name = class_container.qualified_name.as_ref().map(|q| get_full_type_name(q, context));
+                    create_node(name, &constant_name, NodeKind::FIELD, type_name, class_container);
+                }
+            }
+            "enum_body_declarations" => walk_fields(child, class_container, dataflow, context),
+            _ => {}
+        }
+    }
 }
 
-fn walk_node_class_body<'a, 'b>(node: tree_sitter::Node<'a>, class_container: &'b mut Container<'a>, dataflow: &mut DataFlow, walk_context: &WalkContext) {
+fn walk_node_class_body<'a>(node: tree_sitter::Node<'a>, class_container: &mut Container<'a>, dataflow: &mut DataFlow, walk_context: &WalkContext<'a>) {
     let mut cursor = node.walk();
     let children = node.children(&mut cursor);
     for child in children {
-        if child.grammar_name() == "method_declaration" {
-            walk_method_declaration(child, class_container, dataflow, walk_context);
+        match child.grammar_name() {
+            "method_declaration" | "annotation_type_element_declaration" => {
+                walk_method_declaration(child, ContainerKind::FUNCTION, class_container, dataflow, walk_context);
+            }
+            "constructor_declaration" | "compact_constructor_declaration" => {
+                walk_method_declaration(child, ContainerKind::CONSTRUCTOR, class_container, dataflow, walk_context);
+            }
+            "static_initializer" => walk_initializer(child, "<clinit>", class_container, dataflow, walk_context),
+            // not <init>, which is the name of the constructors
+            "block" => walk_initializer(child, "<instinit>", class_container, dataflow, walk_context),
+            "enum_constant" => {
+                // a constant with a body is an anonymous class
+                if let (Some(name), Some(body)) = (child.child_by_field_name("name"), child.child_by_field_name("body")) {
+                    let constant_name = get_code_for_node(name, walk_context.code);
+                    let (qualified_name, binary_name) = walk_context.nested_type_names(&constant_name);
+                    let mut container = Container::new(Some(constant_name), ContainerKind::CLASS);
+                    container.ts_node = Some(child);
+                    container.qualified_name = Some(qualified_name);
+                    container.binary_name = Some(binary_name);
+                    // the class of the constant extends the enum
+                    container.superclass = class_container.qualified_name.as_ref().map(|q| get_full_type_name(q, walk_context));
+                    walk_type_body(body, &mut container, dataflow, walk_context);
+                    class_container.containers.push(Arc::new(container));
+                }
+            }
+            "enum_body_declarations" => walk_node_class_body(child, class_container, dataflow, walk_context),
+            grammar_name if TYPE_DECLARATIONS.contains(&grammar_name) => {
+                walk_type_declaration(child, class_container, dataflow, walk_context);
+            }
+            _ => {}
         }
     }
 }
 
-fn walk_node_class<'a, 'b>(node: tree_sitter::Node<'a>, file_container: &'b mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext) {
+/// Walk the body of a type: the fields first, so that they are visible
+/// from all the members, then the members.
+fn walk_type_body<'a>(body: tree_sitter::Node<'a>, container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
+    walk_fields(body, container, dataflow, context);
+    let body_context = context.with_scope(container);
+    walk_node_class_body(body, container, dataflow, &body_context);
+}
+
+/// Returns the fully qualified names of the superclass and of the interfaces of a type
+/// declaration (`extends` of an interface declares interfaces).
+fn get_supertypes(node: tree_sitter::Node, context: &WalkContext) -> (Option<String>, Vec<String>) {
+    let superclass = node
+        .child_by_field_name("superclass")
+        .and_then(|s| s.named_child(0))
+        .map(|t| qualify_type_name(&get_code_for_node(t, context.code), context));
+
+    let mut cursor = node.walk();
+    let interfaces = node
+        .named_children(&mut cursor)
+        .filter(|c| c.grammar_name() == "super_interfaces" || c.grammar_name() == "extends_interfaces")
+        .filter_map(|c| c.named_child(0))
+        .flat_map(|type_list| {
+            let mut list_cursor = type_list.walk();
+            let types: Vec<String> = type_list.named_children(&mut list_cursor).map(|t| qualify_type_name(&get_code_for_node(t, context.code), context)).collect();
+            types
+        })
+        .collect();
+
+    (superclass, interfaces)
+}
+
+/// Walk a class, an interface, an enum, a record or an annotation type.
+fn walk_type_declaration<'a>(node: tree_sitter::Node<'a>, parent_container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
     let name_node = node.child_by_field_name("name");
 
     if name_node.is_none() {
         return;
     }
 
-    let mut container = Container {
-        name: name_node.map(|n| get_code_for_node(n, context.code)),
-        kind: ContainerKind::CLASS,
-        containers: vec![],
-        nodes: vec![],
-        nodes_by_name: HashMap::new(),
+    let kind = match node.grammar_name() {
+        "interface_declaration" => ContainerKind::INTERFACE,
+        "enum_declaration" => ContainerKind::ENUM,
+        "record_declaration" => ContainerKind::RECORD,
+        "annotation_type_declaration" => ContainerKind::ANNOTATION,
+        _ => ContainerKind::CLASS,
     };
 
-
-    let mut cursor = node.walk();
-    let children = node.children(&mut cursor);
-    for child in children {
-        if child.is_named() {
-            if child.grammar_name() == "class_body" {
-                walk_node_class_body(child, &mut container, dataflow, context);
+    let name = get_code_for_node(name_node.unwrap(), context.code);
+    let (qualified_name, binary_name) = context.nested_type_names(&name);
+    let _span = debug_span!("container", name = qualified_name.as_str(), kind = ?kind).entered();
+    let mut container = Container::new(Some(name), kind);
+    container.ts_node = Some(node);
+    container.qualified_name = Some(qualified_name);
+    container.binary_name = Some(binary_name);
+    let (superclass, interfaces) = get_supertypes(node, context);
+    container.superclass = superclass;
+    container.interfaces = interfaces;
+
+    // the components of a record are its fields
+    if node.grammar_name() == "record_declaration" {
+        if let Some(parameters) = node.child_by_field_name("parameters") {
+            let mut cursor = parameters.walk();
+            for parameter in parameters.named_children(&mut cursor) {
+                if let Some(name) = parameter.child_by_field_name("name") {
+                    let component_name = get_code_for_node(name, context.code);
+                    let type_name = get_declared_type(parameter, context);
+                    create_element_nodes(name, &component_name, &type_name, &mut container);
+                    create_node(name, &component_name, NodeKind::FIELD, type_name, &mut container);
+                }
             }
         }
     }
 
-    file_container.containers.push(Arc::new(container));
+    if let Some(body) = node.child_by_field_name("body") {
+        walk_type_body(body, &mut container, dataflow, context);
+    }
+
+    parent_container.containers.push(Arc::new(container));
 }
 
-pub fn walk_root<'a, 'b>(node: tree_sitter::Node<'a>, file_container: &'b mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext) {
-    // println!("[walk_node] node type: {}", node.grammar_name());
-    if node.grammar_name() == "class_declaration" {
-        return walk_node_class(node, file_container, dataflow, context);
+pub fn walk_root<'a>(node: tree_sitter::Node<'a>, file_container: &mut Container<'a>, dataflow: &mut DataFlow, context: &WalkContext<'a>) {
+    if TYPE_DECLARATIONS.contains(&node.grammar_name()) {
+        return walk_type_declaration(node, file_container, dataflow, context);
     }
 
     let mut cursor = node.walk();
     let children = node.children(&mut cursor);
     for child in children {
         if child.is_named() {
-            // println!("  [walk_node] child type: {}", child.grammar_name());
             walk_root(child, file_container, dataflow, context);
         }
     }
 }
 
-pub fn build_graph(tree: &Tree, code: &str) {
-    let context = WalkContext { code };
-    let mut dataflow = DataFlow {
-        containers: vec![],
-        ts_node_to_df_node: HashMap::new(),
-    };
+/// Returns the package and the imports declared at the top of a file.
+fn get_imports(root: tree_sitter::Node, code: &str) -> Imports {
+    let mut imports = Imports::default();
+    let mut cursor = root.walk();
+    for child in root.named_children(&mut cursor) {
+        let name_opt = child
+            .named_child(0)
+            .filter(|n| n.grammar_name() == "scoped_identifier" || n.grammar_name() == "identifier")
+            .map(|n| get_code_for_node(n, code));
+
+        match (child.grammar_name(), name_opt) {
+            ("package_declaration", Some(name)) => imports.package = Some(name),
+            // static imports are members, not types
+            ("import_declaration", Some(_)) if get_code_for_node(child, code).contains("static ") => {}
+            ("import_declaration", Some(name)) if get_nodes_of_type(child, "asterisk").is_empty() => {
+                let simple_name = get_simple_type_name(&name);
+                imports.types.insert(simple_name, name);
+            }
+            ("import_declaration", Some(name)) => imports.packages.push(name),
+            _ => {}
+        }
+    }
+    imports
+}
 
-    let mut container = Container {
-        name: Some("myfile.java".to_string()),
-        kind: ContainerKind::FILE,
-        containers: vec![],
-        nodes: vec![],
-        nodes_by_name: HashMap::new(),
+/// Build the graph of a file, in a FILE container named by the path of the file. The hierarchy
+/// gives the types declared by all the files of the project, it may be empty.
+pub fn build_graph<'a>(tree: &'a Tree, code: &'a str, path: &str, library: &'a Library, hierarchy: Arc<TypeHierarchy>) -> DataFlow<'a> {
+    let _span = info_span!("file", path).entered();
+    let context = WalkContext {
+        code,
+        library,
+        hierarchy,
+        imports: Arc::new(get_imports(tree.root_node(), code)),
+        scopes: vec![],
     };
+    let mut dataflow = DataFlow::default();
+
+    let mut container = Container::new(Some(path.to_string()), ContainerKind::FILE);
+    container.ts_node = Some(tree.root_node());
+    container.package = context.imports.package.clone();
 
 
     walk_root(tree.root_node(), &mut container, &mut dataflow, &context);
+    if tree.root_node().has_error() {
+        warn!("syntax errors, the graph may be incomplete");
+    }
     dataflow.containers.push(Arc::new(container));
-    dataflow.print_graph();
+    debug!(nodes = dataflow.get_function_nodes().len(), "graph built");
+    dataflow
+}
+
+/// The types known in a file (its imports, the types of the project and of the library), to
+/// match the exceptions thrown in its code with its catch clauses outside of the walk.
+pub struct FileTypes<'a> {
+    context: WalkContext<'a>,
+}
+
+impl<'a> FileTypes<'a> {
+    pub fn new(tree: &'a Tree, code: &'a str, library: &'a Library, hierarchy: Arc<TypeHierarchy>) -> FileTypes<'a> {
+        FileTypes {
+            context: WalkContext {
+                code,
+                library,
+                hierarchy,
+                imports: Arc::new(get_imports(tree.root_node(), code)),
+                scopes: vec![],
+            },
+        }
+    }
+
+    /// Returns true if a catch clause catches an exception, both given by their names in the file.
+    pub fn catches(&self, catch_type: &str, thrown_type: &str) -> bool {
+        catches(&qualify_type_name(catch_type, &self.context), &qualify_type_name(thrown_type, &self.context), &self.context)
+    }
+}
+
+/// Build the graph of several files, given by their path, code and tree. The files are walked
+/// twice: the types of all the files are needed to match the exceptions with the catch clauses.
+pub fn build_project_graph<'a>(files: &[(&str, &'a str, &'a Tree)], library: &'a Library) -> DataFlow<'a> {
+    let build = |hierarchy: Arc<TypeHierarchy>| {
+        let mut dataflow = DataFlow::default();
+        for (path, code, tree) in files {
+            dataflow.extend(build_graph(tree, code, path, library, hierarchy.clone()));
+        }
+        dataflow
+    };
+    build(Arc::new(TypeHierarchy::from_dataflow(&build(Arc::default()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tree_sitter::Tree;
+
+    use super::build_project_graph;
+    use crate::dataflow::model::{Container, ContainerKind, Node, NodeKind};
+    use crate::dataflow::testing::{bundled_library, parse_java};
+
+    /// Returns the FILE container of a file walked as the single file of a project.
+    fn walk<'a>(tree: &'a Tree, code: &'a str) -> Arc<Container<'a>> {
+        build_project_graph(&[("Test.java", code, tree)], bundled_library()).containers.remove(0)
+    }
+
+    /// Returns the first container with a name, in depth-first order.
+    fn find<'c, 'a>(container: &'c Container<'a>, name: &str) -> Option<&'c Container<'a>> {
+        if container.name.as_deref() == Some(name) {
+            return Some(container);
+        }
+        container.containers.iter().find_map(|c| find(c, name))
+    }
+
+    fn names(container: &Container) -> Vec<String> {
+        container.nodes.iter().filter_map(|n| n.name.clone()).collect()
+    }
+
+    #[test]
+    fn walks_the_kinds_of_types() {
+        let code = r#"
+interface Shape { double area(); }
+enum Color { RED, GREEN }
+record Point(int x, int y) {}
+@interface Marker { String value(); }
+"#;
+        let tree = parse_java(code);
+        let file = walk(&tree, code);
+
+        assert!(matches!(find(&file, "Shape").unwrap().kind, ContainerKind::INTERFACE));
+        assert!(matches!(find(&file, "area").unwrap().kind, ContainerKind::FUNCTION));
+        let color = find(&file, "Color").unwrap();
+        assert!(matches!(color.kind, ContainerKind::ENUM));
+        assert_eq!(names(color), vec!["RED", "GREEN"]);
+        let point = find(&file, "Point").unwrap();
+        assert!(matches!(point.kind, ContainerKind::RECORD));
+        assert_eq!(names(point), vec!["x", "y"]);
+        assert!(matches!(find(&file, "Marker").unwrap().kind, ContainerKind::ANNOTATION));
+        assert!(find(&file, "value").is_some());
+    }
+
+    #[test]
+    fn a_constructor_assigns_its_parameters_to_the_fields() {
+        let code = r#"
+class Account {
+    private String owner;
+
+    Account(String name) {
+        owner = name;
+    }
+}
+"#;
+        let tree = parse_java(code);
+        let file = walk(&tree, code);
+
+        let constructor = file.containers[0].containers.iter().find(|c| matches!(c.kind, ContainerKind::CONSTRUCTOR)).unwrap();
+        let name = constructor.nodes_by_name.get("name").unwrap();
+        assert!(matches!(name.kind, NodeKind::PARAMETER));
+        let outbound = name.outbound.read().unwrap();
+        assert_eq!(outbound.len(), 1);
+        assert_eq!(outbound[0].name.as_deref(), Some("owner"));
+        assert!(matches!(outbound[0].kind, NodeKind::FIELD));
+    }
+
+    #[test]
+    fn the_parameters_of_a_compact_constructor_flow_to_the_components() {
+        let code = r#"
+record Range(int low, int high) {
+    Range {
+        if (low > high) throw new IllegalArgumentException();
+    }
 }
+"#;
+        let tree = parse_java(code);
+        let file = walk(&tree, code);
+
+        let range = find(&file, "Range").unwrap();
+        let low = range.nodes_by_name.get("low").unwrap();
+        let inbound = low.inbound.read().unwrap();
+        assert_eq!(inbound.len(), 1);
+        assert!(matches!(inbound[0].kind, NodeKind::PARAMETER));
+    }
+
+    #[test]
+    fn the_initializers_have_their_own_containers() {
+        let code = r#"
+class Config {
+    static String mode;
+
+    static {
+        String first = "a";
+        mode = first;
+    }
+
+    {
+        String second = "b";
+    }
+}
+"#;
+        let tree = parse_java(code);
+        let file = walk(&tree, code);
+
+        let static_initializer = find(&file, "<clinit>").unwrap();
+        assert!(matches!(static_initializer.kind, ContainerKind::INITIALIZER));
+        assert_eq!(names(static_initializer), vec!["first"]);
+        let first = static_initializer.nodes_by_name.get("first").unwrap();
+        assert_eq!(first.outbound.read().unwrap()[0].name.as_deref(), Some("mode"));
+        assert_eq!(names(find(&file, "<instinit>").unwrap()), vec!["second"]);
+    }
+
+    #[test]
+    fn the_nested_types_have_qualified_and_binary_names() {
+        let code = r#"
+class Outer {
+    static class Nested {
+        enum Mode { ON, OFF }
+    }
+}
+"#;
+        let tree = parse_java(code);
+        let file = walk(&tree, code);
+
+        let mode = find(&file, "Mode").unwrap();
+        assert_eq!(mode.qualified_name.as_deref(), Some("Outer.Nested.Mode"));
+        assert_eq!(mode.binary_name.as_deref(), Some("Outer$Nested$Mode"));
+        assert_eq!(find(&file, "Outer").unwrap().binary_name.as_deref(), Some("Outer"));
+    }
+
+    #[test]
+    fn an_inner_class_assigns_the_fields_of_the_enclosing_instance() {
+        let code = r#"
+class Outer {
+    String name;
+
+    class Inner {
+        String name;
+
+        void copy(String value) {
+            Outer.this.name = value;
+            this.name = value;
+        }
+    }
+}
+"#;
+        let tree = parse_java(code);
+        let file = walk(&tree, code);
+
+        let outer_name = find(&file, "Outer").unwrap().nodes_by_name.get("name").unwrap().clone();
+        let inner_name = find(&file, "Inner").unwrap().nodes_by_name.get("name").unwrap().clone();
+        let value = find(&file, "copy").unwrap().nodes_by_name.get("value").unwrap().clone();
+        let outbound = value.outbound.read().unwrap();
+        assert_eq!(outbound.len(), 2);
+        assert!(Arc::ptr_eq(&outbound[0], &outer_name));
+        assert!(Arc::ptr_eq(&outbound[1], &inner_name));
+    }
+
+    const EXCEPTIONS: &str = r#"
+class Test {
+    void caught(String s) {
+        try {
+            throw new IllegalStateException(s);
+        } catch (IllegalArgumentException wrong) {
+        } catch (IllegalStateException e) {
+        }
+    }
+
+    void escapes(String s) throws Exception {
+        try {
+            throw new Exception(s);
+        } catch (IllegalStateException e) {
+        }
+    }
+
+    void rethrown(String s) {
+        try {
+            throw Errors.failure(s);
+        } catch (IllegalStateException first) {
+        } catch (RuntimeException second) {
+        }
+    }
+
+    void lambda(String s) {
+        Runnable r = () -> { throw new IllegalStateException(s); };
+    }
+}
+"#;
+
+    fn inbound_names(container: &Container, name: &str) -> Vec<String> {
+        let node = container.nodes_by_name.get(name).unwrap();
+        let inbound = node.inbound.read().unwrap();
+        inbound.iter().filter_map(|n| n.name.clone()).collect()
+    }
+
+    /// Returns true if the value of a node of a container flows into another one,
+    /// directly or through intermediate nodes (e.g. the calls).
+    fn reaches(container: &Container, from: &str, to: &str) -> bool {
+        let target = container.nodes_by_name.get(to).unwrap();
+        let mut visited: Vec<Arc<Node>> = vec![];
+        let mut to_visit = vec![container.nodes_by_name.get(from).unwrap().clone()];
+        while let Some(current) = to_visit.pop() {
+            for next in current.outbound.read().unwrap().iter() {
+                if Arc::ptr_eq(next, target) {
+                    return true;
+                }
+                if !visited.iter().any(|v| Arc::ptr_eq(v, next)) {
+                    visited.push(next.clone());
+                    to_visit.push(next.clone());
+                }
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn a_thrown_value_flows_to_the_matching_catch_parameter() {
+        let tree = parse_java(EXCEPTIONS);
+        let file = walk(&tree, EXCEPTIONS);
+
+        let caught = find(&file, "caught").unwrap();
+        assert!(reaches(caught, "s", "e"));
+        assert!(!reaches(caught, "s", "wrong"));
+        assert!(!caught.nodes_by_name.contains_key("<throws>"));
+    }
+
+    #[test]
+    fn an_uncaught_exception_is_thrown_by_the_function() {
+        let tree = parse_java(EXCEPTIONS);
+        let file = walk(&tree, EXCEPTIONS);
+
+        let escapes = find(&file, "escapes").unwrap();
+        assert!(!reaches(escapes, "s", "e"));
+        let throws = escapes.nodes_by_name.get("<throws>").unwrap();
+        assert!(matches!(throws.kind, NodeKind::EXCEPTION));
+        assert!(reaches(escapes, "s", "<throws>"));
+    }
+
+    #[test]
+    fn an_exception_of_an_unknown_type_may_be_caught_by_all_the_catch_clauses() {
+        let tree = parse_java(EXCEPTIONS);
+        let file = walk(&tree, EXCEPTIONS);
+
+        // the type returned by a method of a dependency is unknown
+        let rethrown = find(&file, "rethrown").unwrap();
+        assert!(reaches(rethrown, "s", "first"));
+        assert!(reaches(rethrown, "s", "second"));
+        assert!(reaches(rethrown, "s", "<throws>"));
+    }
+
+    #[test]
+    fn an_exception_thrown_in_a_lambda_is_not_thrown_by_the_function() {
+        let tree = parse_java(EXCEPTIONS);
+        let file = walk(&tree, EXCEPTIONS);
+
+        assert!(!find(&file, "lambda").unwrap().nodes_by_name.contains_key("<throws>"));
+    }
+
+    const HIERARCHY_EXCEPTIONS: &str = r#"
+import java.io.*;
+
+class Test {
+    void subclass(String s) {
+        try {
+            throw new FileNotFoundException(s);
+        } catch (IOException e) {
+        }
+    }
+
+    void unrelated(String s) throws Exception {
+        try {
+            throw new Exception(s);
+        } catch (IOException e) {
+        }
+    }
+
+    void declared(String s) {
+        try {
+            throw new InvalidInput(s);
+        } catch (IllegalArgumentException e) {
+        }
+    }
+
+    void anonymous() {
+        Runnable r = new Runnable() {
+            public void run() {
+                throw new IllegalStateException("run");
+            }
+        };
+    }
+}
+
+class InvalidInput extends IllegalArgumentException {
+    InvalidInput(String s) {}
+}
+"#;
+
+    #[test]
+    fn catches_the_subtypes_of_the_caught_type() {
+        let tree = parse_java(HIERARCHY_EXCEPTIONS);
+        let file = walk(&tree, HIERARCHY_EXCEPTIONS);
+
+        // through the library, then through the hierarchy of the analyzed code
+        let subclass = find(&file, "subclass").unwrap();
+        assert!(reaches(subclass, "s", "e"));
+        assert!(!subclass.nodes_by_name.contains_key("<throws>"));
+        assert!(reaches(find(&file, "declared").unwrap(), "s", "e"));
+    }
+
+    #[test]
+    fn does_not_catch_the_supertypes_of_the_caught_type() {
+        let tree = parse_java(HIERARCHY_EXCEPTIONS);
+        let file = walk(&tree, HIERARCHY_EXCEPTIONS);
+
+        let unrelated = find(&file, "unrelated").unwrap();
+        assert!(!reaches(unrelated, "s", "e"));
+        assert!(reaches(unrelated, "s", "<throws>"));
+    }
+
+    const ERRORS: &str = r#"
+import com.acme.ParseError;
+
+class Test {
+    void unknown(String s) {
+        try {
+            throw new ParseError(s);
+        } catch (Exception e) {
+        }
+    }
+
+    void error(String s) {
+        try {
+            throw new Fatal(s);
+        } catch (Exception e) {
+        } catch (Error f) {
+        }
+    }
+}
+
+class Fatal extends Error {
+    Fatal(String s) {}
+}
+"#;
+
+    #[test]
+    fn the_errors_are_found_by_their_supertypes_not_by_their_names() {
+        let tree = parse_java(ERRORS);
+        let file = walk(&tree, ERRORS);
+
+        // the supertypes of an exception of a dependency are unknown, it is not an error
+        let unknown = find(&file, "unknown").unwrap();
+        assert!(reaches(unknown, "s", "e"));
+        let error = find(&file, "error").unwrap();
+        assert!(!reaches(error, "s", "e"));
+        assert!(reaches(error, "s", "f"));
+    }
+
+    #[test]
+    fn the_exceptions_of_an_initializer_block_are_thrown_by_the_initializer() {
+        let code = r#"
+class Test {
+    {
+        String name = System.getenv("NAME");
+        try {
+            throw new IllegalStateException(name);
+        } catch (IllegalArgumentException e) {
+        }
+    }
+}
+"#;
+        let tree = parse_java(code);
+        let file = walk(&tree, code);
+
+        let initializer = find(&file, "<instinit>").unwrap();
+        assert!(!reaches(initializer, "name", "e"));
+        assert!(reaches(initializer, "name", "<throws>"));
+    }
+
+    #[test]
+    fn the_exceptions_of_an_anonymous_class_are_not_thrown_by_the_enclosing_method() {
+        let tree = parse_java(HIERARCHY_EXCEPTIONS);
+        let file = walk(&tree, HIERARCHY_EXCEPTIONS);
+
+        assert!(!find(&file, "anonymous").unwrap().nodes_by_name.contains_key("<throws>"));
+    }
+
+    #[test]
+    fn the_resources_of_a_try_with_resources_are_variables() {
+        let code = r#"
+class Test {
+    void query(DataSource dataSource) throws SQLException {
+        try (Connection connection = dataSource.getConnection(); Statement statement = connection.createStatement()) {
+            statement.execute("select 1");
+        }
+    }
+}
+"#;
+        let tree = parse_java(code);
+        let file = walk(&tree, code);
+
+        let query = find(&file, "query").unwrap();
+        assert!(matches!(query.nodes_by_name.get("connection").unwrap().kind, NodeKind::VARIABLE));
+        assert!(reaches(query, "dataSource", "connection"));
+        assert!(reaches(query, "connection", "statement"));
+    }
+
+    const COLLECTIONS: &str = r#"
+class Test {
+    void list(String p) {
+        List<String> names = new ArrayList<>();
+        names.add(p);
+        String first = names.get(0);
+        for (String name : names) {
+        }
+    }
+
+    void array(String p) {
+        String[] names = new String[1];
+        names[0] = p;
+        String first = names[0];
+    }
+
+    void map(String p) {
+        Map<String, String> values = new HashMap<>();
+        values.put("name", p);
+        String name = values.get("name");
+        String query = values.get("query");
+    }
+}
+"#;
+
+    #[test]
+    fn the_elements_of_a_collection_have_their_own_node() {
+        let tree = parse_java(COLLECTIONS);
+        let file = walk(&tree, COLLECTIONS);
+
+        let list = find(&file, "list").unwrap();
+        assert_eq!(inbound_names(list, "names[]"), vec!["p"]);
+        assert!(inbound_names(list, "first").contains(&"names[]".to_string()));
+        assert!(inbound_names(list, "name").contains(&"names[]".to_string()));
+        let array = find(&file, "array").unwrap();
+        assert_eq!(inbound_names(array, "names[]"), vec!["p"]);
+        assert!(inbound_names(array, "first").contains(&"names[]".to_string()));
+    }
+
+    #[test]
+    fn the_values_of_a_map_are_stored_by_constant_key() {
+        let tree = parse_java(COLLECTIONS);
+        let file = walk(&tree, COLLECTIONS);
+
+        let map = find(&file, "map").unwrap();
+        assert_eq!(inbound_names(map, "values[\"name\"]"), vec!["p"]);
+        assert!(inbound_names(map, "name").contains(&"values[\"name\"]".to_string()));
+        assert!(!inbound_names(map, "query").contains(&"values[\"name\"]".to_string()));
+    }
+
+    #[test]
+    fn a_value_flows_through_the_summaries_of_the_library() {
+        let code = r#"
+class Test {
+    void build(String name) {
+        StringBuilder builder = new StringBuilder();
+        builder.append(name);
+        String query = builder.toString();
+        int length = query.length();
+    }
+}
+"#;
+        let tree = parse_java(code);
+        let file = walk(&tree, code);
+
+        let build = find(&file, "build").unwrap();
+        assert!(reaches(build, "name", "builder"));
+        assert!(reaches(build, "builder", "query"));
+        // the length of a string is not the string
+        assert!(!reaches(build, "name", "length"));
+    }
+
+    #[test]
+    fn all_the_declarators_with_a_value_are_variables() {
+        let code = r#"
+class Test {
+    void declare(String x, String y) {
+        String a = x, b = y, c;
+    }
+}
+"#;
+        let tree = parse_java(code);
+        let file = walk(&tree, code);
+        let declare = find(&file, "declare").unwrap();
+
+        assert_eq!(inbound_names(declare, "a"), vec!["x"]);
+        assert_eq!(inbound_names(declare, "b"), vec!["y"]);
+        assert!(!declare.nodes_by_name.contains_key("c"));
+    }
+}
+
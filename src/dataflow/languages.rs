@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Number of bytes read at the start of a file to find the interpreter of a script.
+const SHEBANG_SIZE: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Go,
+    Java,
+    #[value(name = "javascript")]
+    JavaScript,
+    Jsx,
+    Python,
+    #[value(name = "typescript")]
+    TypeScript,
+    Tsx,
+}
+
+impl Language {
+    /// Returns the name of the language in lower case, as written in the configurations.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Language::Go => "go",
+            Language::Java => "java",
+            Language::JavaScript => "javascript",
+            Language::Jsx => "jsx",
+            Language::Python => "python",
+            Language::TypeScript => "typescript",
+            Language::Tsx => "tsx",
+        }
+    }
+
+    pub fn get_tree_sitter_language(&self) -> tree_sitter::Language {
+        match self {
+            Language::Go => tree_sitter_go::language(),
+            Language::Java => tree_sitter_java::language(),
+            // the JavaScript grammar parses JSX
+            Language::JavaScript | Language::Jsx => tree_sitter_javascript::language(),
+            Language::Python => tree_sitter_python::language(),
+            Language::TypeScript => tree_sitter_typescript::language_typescript(),
+            Language::Tsx => tree_sitter_typescript::language_tsx(),
+        }
+    }
+}
+
+/// Additional file names, extensions and interpreters of the languages, in the
+/// `languages` of the configuration of a project, e.g. `{"extensions": {"jav": "java"}}`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LanguagesConfig {
+    pub filenames: HashMap<String, Language>,
+    pub extensions: HashMap<String, Language>,
+    pub interpreters: HashMap<String, Language>,
+}
+
+/// Detects the language of a file from its name, then from its extension, then from
+/// the interpreter of its shebang line (e.g. `#!/usr/bin/env python3`).
+pub struct LanguageRegistry {
+    filenames: HashMap<String, Language>,
+    extensions: HashMap<String, Language>,
+    interpreters: HashMap<String, Language>,
+}
+
+impl Default for LanguageRegistry {
+    fn default() -> Self {
+        let to_map = |entries: &[(&str, Language)]| entries.iter().map(|(name, language)| (name.to_string(), *language)).collect();
+        LanguageRegistry {
+            filenames: to_map(&[("SConstruct", Language::Python), ("SConscript", Language::Python), ("Jakefile", Language::JavaScript)]),
+            extensions: to_map(&[
+                ("go", Language::Go),
+                ("java", Language::Java),
+                ("js", Language::JavaScript),
+                ("mjs", Language::JavaScript),
+                ("cjs", Language::JavaScript),
+                ("jsx", Language::Jsx),
+                ("py", Language::Python),
+                ("pyi", Language::Python),
+                ("pyw", Language::Python),
+                ("ts", Language::TypeScript),
+                ("mts", Language::TypeScript),
+                ("cts", Language::TypeScript),
+                ("tsx", Language::Tsx),
+            ]),
+            interpreters: to_map(&[
+                ("python", Language::Python),
+                ("python2", Language::Python),
+                ("python3", Language::Python),
+                ("node", Language::JavaScript),
+                ("nodejs", Language::JavaScript),
+                ("ts-node", Language::TypeScript),
+            ]),
+        }
+    }
+}
+
+impl LanguageRegistry {
+    /// Returns the registry of the built-in languages with the names of a configuration.
+    pub fn new(config: &LanguagesConfig) -> LanguageRegistry {
+        let mut registry = LanguageRegistry::default();
+        registry.filenames.extend(config.filenames.iter().map(|(k, v)| (k.clone(), *v)));
+        registry.extensions.extend(config.extensions.iter().map(|(k, v)| (k.trim_start_matches('.').to_string(), *v)));
+        registry.interpreters.extend(config.interpreters.iter().map(|(k, v)| (k.clone(), *v)));
+        registry
+    }
+
+    pub fn detect(&self, path: &Path) -> Option<Language> {
+        let filename = path.file_name().and_then(|f| f.to_str());
+        if let Some(language) = filename.and_then(|f| self.filenames.get(f)) {
+            return Some(*language);
+        }
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| self.extensions.get(e).copied())
+            .or_else(|| self.detect_interpreter(path))
+    }
+
+    /// Returns the language of the interpreter of a script, written `#!/usr/bin/python3`
+    /// or `#!/usr/bin/env -S node --flag`.
+    fn detect_interpreter(&self, path: &Path) -> Option<Language> {
+        let mut header = vec![0; SHEBANG_SIZE];
+        let length = File::open(path).and_then(|mut f| f.read(&mut header)).ok()?;
+        let header = String::from_utf8_lossy(&header[..length]);
+        let line = header.lines().next()?.strip_prefix("#!")?;
+        let mut words = line.split_whitespace();
+        let mut interpreter = words.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            interpreter = words.find(|w| !w.starts_with('-'))?;
+        }
+        self.interpreters.get(interpreter).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::testing::temp_dir;
+
+    #[test]
+    fn detects_the_languages_from_the_names_and_the_extensions() {
+        let registry = LanguageRegistry::default();
+
+        assert_eq!(registry.detect(Path::new("src/Task.java")), Some(Language::Java));
+        assert_eq!(registry.detect(Path::new("web/app.mjs")), Some(Language::JavaScript));
+        assert_eq!(registry.detect(Path::new("web/App.tsx")), Some(Language::Tsx));
+        assert_eq!(registry.detect(Path::new("SConstruct")), Some(Language::Python));
+        assert_eq!(registry.detect(Path::new("README.md")), None);
+    }
+
+    #[test]
+    fn detects_the_languages_from_the_interpreters() {
+        let directory = temp_dir("languages");
+        let scripts = [("deploy", "#!/usr/bin/env -S python3 -u\nprint()\n"), ("serve", "#!/usr/local/bin/node\n"), ("build", "#!/bin/sh\n")];
+        for (name, content) in scripts {
+            std::fs::write(directory.join(name), content).unwrap();
+        }
+        let registry = LanguageRegistry::default();
+
+        assert_eq!(registry.detect(&directory.join("deploy")), Some(Language::Python));
+        assert_eq!(registry.detect(&directory.join("serve")), Some(Language::JavaScript));
+        assert_eq!(registry.detect(&directory.join("build")), None);
+    }
+
+    #[test]
+    fn the_configuration_adds_names_and_extensions() {
+        let config: LanguagesConfig = serde_json::from_str(r#"{"extensions": {".jav": "java", "js": "typescript"}, "filenames": {"Justfile": "python"}}"#).unwrap();
+        let registry = LanguageRegistry::new(&config);
+
+        assert_eq!(registry.detect(Path::new("Task.jav")), Some(Language::Java));
+        assert_eq!(registry.detect(Path::new("app.js")), Some(Language::TypeScript));
+        assert_eq!(registry.detect(Path::new("Justfile")), Some(Language::Python));
+    }
+
+    #[test]
+    fn the_grammars_are_compatible_with_the_parser() {
+        let languages = [Language::Go, Language::Java, Language::JavaScript, Language::Jsx, Language::Python, Language::TypeScript, Language::Tsx];
+        for language in languages {
+            let mut parser = tree_sitter::Parser::new();
+            assert!(parser.set_language(&language.get_tree_sitter_language()).is_ok(), "{}", language.get_name());
+            assert!(parser.parse("x", None).is_some(), "{}", language.get_name());
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+/// Summaries of the JDK and servlet APIs, bundled with the binaries.
+const BUNDLED_LIBRARY: &str = include_str!("library.json");
+
+const OBJECT_TYPE: &str = "java.lang.Object";
+
+/// The primitive types with the types they are widened to and their boxed type.
+const PRIMITIVE_TYPES: [(&str, &[&str], &str); 8] = [
+    ("boolean", &[], "java.lang.Boolean"),
+    ("byte", &["short", "int", "long", "float", "double"], "java.lang.Byte"),
+    ("short", &["int", "long", "float", "double"], "java.lang.Short"),
+    ("char", &["int", "long", "float", "double"], "java.lang.Character"),
+    ("int", &["long", "float", "double"], "java.lang.Integer"),
+    ("long", &["float", "double"], "java.lang.Long"),
+    ("float", &["double"], "java.lang.Float"),
+    ("double", &[], "java.lang.Double"),
+];
+
+pub fn is_primitive_type(type_name: &str) -> bool {
+    PRIMITIVE_TYPES.iter().any(|(primitive, _, _)| *primitive == type_name)
+}
+
+/// Either side of a flow in a method summary.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "String")]
+pub enum Endpoint {
+    Receiver,
+    Return,
+    Argument(usize),
+    // any of the arguments
+    Arguments,
+}
+
+impl TryFrom<&str> for Endpoint {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.trim() {
+            "receiver" => Ok(Endpoint::Receiver),
+            "return" => Ok(Endpoint::Return),
+            "args" => Ok(Endpoint::Arguments),
+            other => other
+                .strip_prefix("arg")
+                .and_then(|index| index.parse::<usize>().ok())
+                .map(Endpoint::Argument)
+                .ok_or_else(|| anyhow!("invalid flow endpoint {}", other)),
+        }
+    }
+}
+
+impl TryFrom<String> for Endpoint {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Endpoint::try_from(value.as_str())
+    }
+}
+
+/// A flow between the receiver, the arguments and the value returned by a method,
+/// written `arg0 -> return`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Flow {
+    pub from: Endpoint,
+    pub to: Endpoint,
+}
+
+impl TryFrom<String> for Flow {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let (from, to) = value.split_once("->").ok_or_else(|| anyhow!("invalid flow {}, expected <from> -> <to>", value))?;
+        Ok(Flow {
+            from: Endpoint::try_from(from)?,
+            to: Endpoint::try_from(to)?,
+        })
+    }
+}
+
+/// How the values flow through a method that is not analyzed.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MethodSummary {
+    // fully qualified name of the type declaring the method
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub method: String,
+    // fully qualified names of the parameter types, when the summary is for a single overload
+    #[serde(default)]
+    pub parameters: Option<Vec<String>>,
+    // fully qualified name of the returned type, to follow chained invocations
+    pub returns: Option<String>,
+    #[serde(default)]
+    pub flows: Vec<Flow>,
+    // values cleaned by the method (e.g. `arg0` for an argument escaped by the method)
+    #[serde(default)]
+    pub sanitizes: Vec<Endpoint>,
+}
+
+impl MethodSummary {
+    /// Returns true if the value of an endpoint is cleaned by the method.
+    pub fn sanitizes(&self, endpoint: &Endpoint) -> bool {
+        self.sanitizes.iter().any(|s| s == endpoint || (*s == Endpoint::Arguments && matches!(endpoint, Endpoint::Argument(_))))
+    }
+}
+
+#[derive(Deserialize)]
+struct LibraryType {
+    name: String,
+    #[serde(default)]
+    supertypes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct LibraryFile {
+    #[serde(default)]
+    types: Vec<LibraryType>,
+    #[serde(default)]
+    methods: Vec<MethodSummary>,
+}
+
+/// The method summaries of the libraries, by type and method name.
+#[derive(Default)]
+pub struct Library {
+    // a method may have a summary for all its overloads and summaries for some of them
+    summaries: HashMap<(String, String), Vec<MethodSummary>>,
+    supertypes: HashMap<String, Vec<String>>,
+    types: HashSet<String>,
+}
+
+impl Library {
+    /// Returns the library with the bundled summaries of the JDK and servlet APIs.
+    pub fn bundled() -> Result<Library> {
+        let mut library = Library::default();
+        library.add_json(BUNDLED_LIBRARY)?;
+        Ok(library)
+    }
+
+    /// Add the types and summaries of a JSON document. A summary replaces any
+    /// existing summary of the same method.
+    pub fn add_json(&mut self, json: &str) -> Result<()> {
+        let file: LibraryFile = serde_json::from_str(json)?;
+        for library_type in file.types {
+            self.types.insert(library_type.name.clone());
+            self.supertypes.entry(library_type.name).or_default().extend(library_type.supertypes);
+        }
+        for summary in file.methods {
+            self.add_summary(summary);
+        }
+        Ok(())
+    }
+
+    /// Add a summary, replacing the existing summary of the same method and parameters.
+    pub fn add_summary(&mut self, summary: MethodSummary) {
+        self.types.insert(summary.type_name.clone());
+        let summaries = self.summaries.entry((summary.type_name.clone(), summary.method.clone())).or_default();
+        summaries.retain(|s| s.parameters != summary.parameters);
+        summaries.push(summary);
+    }
+
+    pub fn add_type(&mut self, type_name: &str) {
+        self.types.insert(type_name.to_string());
+    }
+
+    /// Returns true if the type is known, either with a supertype or a method summary.
+    pub fn has_type(&self, type_name: &str) -> bool {
+        self.types.contains(type_name)
+    }
+
+    /// Returns true if a type is the same as another type or one of its subtypes.
+    pub fn is_subtype(&self, type_name: &str, supertype: &str) -> bool {
+        supertype == OBJECT_TYPE || self.get_all_supertypes(type_name).iter().any(|t| t == supertype)
+    }
+
+    /// Returns true if a value of a type can be given to a parameter of another type: the same
+    /// type or a subtype, or a primitive type widened, boxed or unboxed (e.g. an int for a long
+    /// or an Integer).
+    pub fn is_assignable(&self, type_name: &str, parameter_type: &str) -> bool {
+        if self.is_subtype(type_name, parameter_type) {
+            return true;
+        }
+        PRIMITIVE_TYPES.iter().any(|(primitive, widened, boxed)| {
+            let converted = |t: &str| t == *primitive || widened.contains(&t);
+            (*primitive == type_name && (widened.contains(&parameter_type) || self.is_subtype(boxed, parameter_type))) || (*boxed == type_name && converted(parameter_type))
+        })
+    }
+
+    /// Returns a type followed by all its known supertypes.
+    fn get_all_supertypes(&self, type_name: &str) -> Vec<String> {
+        let mut to_visit = vec![type_name.to_string()];
+        let mut visited: Vec<String> = vec![];
+
+        while let Some(current) = to_visit.pop() {
+            if let Some(supertypes) = self.supertypes.get(&current) {
+                to_visit.extend(supertypes.iter().filter(|s| !visited.contains(s)).cloned());
+            }
+            visited.push(current);
+        }
+
+        visited
+    }
+
+    /// Returns true if arguments of the given types (None when unknown) can be given to parameters.
+    pub fn accepts(&self, parameters: &[String], argument_types: &[Option<String>]) -> bool {
+        parameters.len() == argument_types.len() && parameters.iter().zip(argument_types).all(|(p, a)| a.as_ref().is_none_or(|a| self.is_assignable(a, p)))
+    }
+
+    /// Returns the summary of a method of a type invoked with arguments of the given types (None
+    /// when unknown), declared by the type or one of its supertypes. A summary of the most specific
+    /// overload accepting the arguments is preferred to a summary of all the overloads, e.g.
+    /// `String.valueOf(char[])` to `String.valueOf(Object)` for a `char[]`. All the types inherit
+    /// the methods of `java.lang.Object`.
+    pub fn get_summary(&self, type_name: &str, method: &str, argument_types: &[Option<String>]) -> Option<&MethodSummary> {
+        let mut to_visit = vec![OBJECT_TYPE.to_string(), type_name.to_string()];
+        let mut visited: Vec<String> = vec![];
+
+        while let Some(current) = to_visit.pop() {
+            if let Some(summaries) = self.summaries.get(&(current.clone(), method.to_string())) {
+                let overloads: Vec<(&MethodSummary, &Vec<String>)> = summaries
+                    .iter()
+                    .filter_map(|s| s.parameters.as_ref().map(|p| (s, p)))
+                    .filter(|(_, parameters)| self.accepts(parameters, argument_types))
+                    .collect();
+                // the overload whose parameters can be given to the parameters of all the others
+                let most_specific = overloads.iter().find(|(_, parameters)| {
+                    let types: Vec<Option<String>> = parameters.iter().cloned().map(Some).collect();
+                    overloads.iter().all(|(_, others)| self.accepts(others, &types))
+                });
+                let overload = most_specific.or(overloads.first()).map(|(s, _)| *s);
+                if let Some(summary) = overload.or_else(|| summaries.iter().find(|s| s.parameters.is_none())) {
+                    return Some(summary);
+                }
+            }
+            if let Some(supertypes) = self.supertypes.get(&current) {
+                to_visit.extend(supertypes.iter().filter(|s| !visited.contains(s)).cloned());
+            }
+            visited.push(current);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_flows() {
+        let flow = Flow::try_from("arg1 -> return".to_string()).unwrap();
+
+        assert_eq!((flow.from, flow.to), (Endpoint::Argument(1), Endpoint::Return));
+        assert!(Flow::try_from("receiver".to_string()).is_err());
+        assert!(Flow::try_from("argx -> return".to_string()).is_err());
+    }
+
+    #[test]
+    fn the_summaries_are_inherited_from_the_supertypes() {
+        let library = Library::bundled().unwrap();
+
+        // HttpServletRequestWrapper -> HttpServletRequest -> ServletRequest
+        let summary = library.get_summary("javax.servlet.http.HttpServletRequestWrapper", "getParameter", &[None]).unwrap();
+        assert_eq!(summary.type_name, "javax.servlet.ServletRequest");
+        // all the types are objects
+        assert_eq!(library.get_summary("app.Unknown", "toString", &[]).unwrap().type_name, OBJECT_TYPE);
+        assert!(library.get_summary("app.Unknown", "getParameter", &[None]).is_none());
+    }
+
+    #[test]
+    fn the_summary_of_an_overload_is_chosen_by_the_types_of_the_arguments() {
+        let mut library = Library::bundled().unwrap();
+        library
+            .add_json(
+                r#"{"methods": [
+  {"type": "java.lang.String", "method": "valueOf", "parameters": ["char[]"], "returns": "java.lang.String", "flows": ["arg0 -> return"]},
+  {"type": "java.lang.String", "method": "valueOf", "parameters": ["java.lang.Object"], "returns": "java.lang.String", "flows": []}
+]}"#,
+            )
+            .unwrap();
+        let parameters = |argument_type: Option<&str>| library.get_summary("java.lang.String", "valueOf", &[argument_type.map(String::from)]).and_then(|s| s.parameters.clone());
+
+        assert_eq!(parameters(Some("char[]")), Some(vec!["char[]".to_string()]));
+        assert_eq!(parameters(Some("java.util.List")), Some(vec![OBJECT_TYPE.to_string()]));
+        // an int is boxed, the most specific overload is chosen when the type is unknown
+        assert_eq!(parameters(Some("int")), Some(vec![OBJECT_TYPE.to_string()]));
+        assert_eq!(parameters(None), Some(vec!["char[]".to_string()]));
+        // the summary of all the overloads
+        assert!(library.get_summary("java.lang.String", "valueOf", &[None, None]).is_some_and(|s| s.parameters.is_none()));
+    }
+
+    #[test]
+    fn the_primitive_types_are_widened_and_boxed() {
+        let library = Library::bundled().unwrap();
+
+        assert!(library.is_assignable("int", "long"));
+        assert!(library.is_assignable("int", "java.lang.Integer"));
+        assert!(library.is_assignable("java.lang.Integer", "int"));
+        assert!(library.is_assignable("char[]", OBJECT_TYPE));
+        assert!(!library.is_assignable("long", "int"));
+        assert!(!library.is_assignable("java.lang.String", "char[]"));
+    }
+
+    #[test]
+    fn a_summary_replaces_the_summary_of_the_same_method() {
+        let mut library = Library::bundled().unwrap();
+        library
+            .add_json(r#"{"methods": [{"type": "java.lang.String", "method": "trim", "flows": []}]}"#)
+            .unwrap();
+
+        assert!(library.get_summary("java.lang.String", "trim", &[]).unwrap().flows.is_empty());
+        assert!(library.has_type("java.lang.String"));
+        assert!(library.add_json(r#"{"methods": [{"type": "A", "method": "m", "flows": ["arg0 => return"]}]}"#).is_err());
+    }
+}
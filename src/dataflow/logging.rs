@@ -0,0 +1,28 @@
+use std::io::IsTerminal;
+
+use anyhow::{anyhow, Result};
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable giving the log level when it is not set on the command line, with the
+/// syntax of the tracing filters (e.g. `debug` or `warn,dataflow_experiments::dataflow::taint=trace`).
+pub const LOG_ENV: &str = "DATAFLOW_LOG";
+
+/// Levels selected by `-v`, `-vv` and `-vvv`.
+const VERBOSE_LEVELS: [&str; 3] = ["info", "debug", "trace"];
+
+/// Send the logs to the standard error, the standard output only carries the results.
+/// The level is given by `level`, then by the number of `-v`, then by [LOG_ENV], and
+/// defaults to the warnings.
+pub fn init_logging(level: Option<&str>, verbosity: u8) -> Result<()> {
+    let filter = match (level, verbosity) {
+        (Some(level), _) => EnvFilter::try_new(level)?,
+        (None, 0) => EnvFilter::try_from_env(LOG_ENV).unwrap_or_else(|_| EnvFilter::new("warn")),
+        (None, v) => EnvFilter::new(VERBOSE_LEVELS[(v as usize).min(VERBOSE_LEVELS.len()) - 1]),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .try_init()
+        .map_err(|e| anyhow!(e))
+}
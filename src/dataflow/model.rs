@@ -1,21 +1,37 @@
 use std::collections::HashMap;
-use std::hash::Hash;
+use std::io::Write;
 use std::sync::{Arc, RwLock};
 
-use tree_sitter;
-
 const PRINT_INDENTATION: usize = 3;
 
-#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ContainerKind {
     CLASS,
+    INTERFACE,
+    ENUM,
+    RECORD,
+    ANNOTATION,
     FUNCTION,
+    CONSTRUCTOR,
+    INITIALIZER,
     FILE,
 }
 
 pub struct Container<'a> {
     pub name: Option<String>,
+    // name of a type as written in the source code (e.g. Outer.Inner)
+    pub qualified_name: Option<String>,
+    // name of a type as generated by the compiler (e.g. Outer$Inner)
+    pub binary_name: Option<String>,
     pub kind: ContainerKind,
+    // declaration of a type or a function
+    pub ts_node: Option<tree_sitter::Node<'a>>,
+    // package of a FILE container
+    pub package: Option<String>,
+    // fully qualified names of the supertypes of a type
+    pub superclass: Option<String>,
+    pub interfaces: Vec<String>,
     pub containers: Vec<Arc<Container<'a>>>,
     pub nodes: Vec<Arc<Node<'a>>>,
     pub nodes_by_name: HashMap<String, Arc<Node<'a>>>,
@@ -23,62 +39,245 @@ pub struct Container<'a> {
 }
 
 impl Container<'_> {
-    pub fn print(&self, indent: Option<usize>) {
+    pub fn new(name: Option<String>, kind: ContainerKind) -> Self {
+        Container {
+            name,
+            qualified_name: None,
+            binary_name: None,
+            kind,
+            ts_node: None,
+            package: None,
+            superclass: None,
+            interfaces: vec![],
+            containers: vec![],
+            nodes: vec![],
+            nodes_by_name: HashMap::new(),
+        }
+    }
+
+    /// Returns the fully qualified name of a type, given the package of its file.
+    pub fn get_full_type_name(&self, package: &Option<String>) -> Option<String> {
+        let qualified_name = self.qualified_name.clone()?;
+        match package {
+            Some(package) => Some(format!("{}.{}", package, qualified_name)),
+            None => Some(qualified_name),
+        }
+    }
+
+    pub fn print(&self, out: &mut dyn Write, indent: Option<usize>) -> std::io::Result<()> {
         let name = self.name.clone().unwrap_or("<no name>".to_string());
         let indent = indent.unwrap_or(0);
-        println!("{}[container] name={} kind={:?}", " ".repeat(indent), name, self.kind);
+        match (&self.qualified_name, &self.binary_name) {
+            (Some(qualified_name), Some(binary_name)) => writeln!(out, "{}[container] name={} qualified_name={} binary_name={} kind={:?}", " ".repeat(indent), name, qualified_name, binary_name, self.kind)?,
+            _ => writeln!(out, "{}[container] name={} kind={:?}", " ".repeat(indent), name, self.kind)?,
+        }
 
         for c in &self.containers {
-            c.print(Some(indent + PRINT_INDENTATION))
+            c.print(out, Some(indent + PRINT_INDENTATION))?;
         }
 
         for n in &self.nodes {
-            n.print(Some(indent + PRINT_INDENTATION))
+            n.print(out, Some(indent + PRINT_INDENTATION))?;
         }
+        Ok(())
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug)]
 pub enum NodeKind {
     PARAMETER,
     VARIABLE,
+    FIELD,
+    // elements of a collection or an array, keys or values of a map
+    ELEMENT,
+    // exceptions thrown out of a function
+    EXCEPTION,
+    // value returned by a method invocation or an object creation
+    CALL,
+    // value passed as an argument of a method invocation or an object creation
+    ARGUMENT,
+    // value returned by a function
+    RETURN,
+}
+
+/// Kind of an edge, given by the kinds of the nodes it links.
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdgeKind {
+    ASSIGNMENT,
+    // a value passed as an argument
+    ARGUMENT,
+    // an argument or a receiver flowing into the value returned by a call
+    RETURN,
+    // an argument flowing into the receiver or another argument of a call
+    SIDE_EFFECT,
+    // a value stored into or read from the elements of a collection
+    ELEMENT,
+    // a thrown exception
+    EXCEPTION,
+}
+
+impl EdgeKind {
+    pub fn between(from: &Node, to: &Node) -> EdgeKind {
+        match (&from.kind, &to.kind) {
+            (_, NodeKind::ARGUMENT) => EdgeKind::ARGUMENT,
+            (_, NodeKind::CALL) | (_, NodeKind::RETURN) => EdgeKind::RETURN,
+            (NodeKind::ARGUMENT, _) => EdgeKind::SIDE_EFFECT,
+            (NodeKind::ELEMENT, _) | (_, NodeKind::ELEMENT) => EdgeKind::ELEMENT,
+            (_, NodeKind::EXCEPTION) => EdgeKind::EXCEPTION,
+            _ => EdgeKind::ASSIGNMENT,
+        }
+    }
+}
+
+/// How the method of a call is selected.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CallKind {
+    // new T(...), this(...) or super(...)
+    CONSTRUCTOR,
+    // T.m(...)
+    STATIC,
+    // super.m(...)
+    SUPER,
+    // m(...), this.m(...) or o.m(...), dispatched on the runtime type of the receiver
+    VIRTUAL,
+}
+
+/// The method invoked by a call, for the nodes of the returned value and of the arguments.
+#[derive(Clone, Debug)]
+pub struct Invocation {
+    pub kind: CallKind,
+    // fully qualified name of the type declaring the method, when known
+    pub type_name: Option<String>,
+    // name of the method, <init> for a constructor
+    pub method: String,
+    // number of arguments of the call
+    pub arguments: usize,
+    // fully qualified types of the arguments, None when unknown
+    pub argument_types: Vec<Option<String>>,
+    // index of the argument for an ARGUMENT node
+    pub argument: Option<usize>,
 }
 
 pub struct Node<'a> {
     pub name: Option<String>,
     pub kind: NodeKind,
+    // fully qualified declared type
+    pub type_name: Option<String>,
     pub inbound: RwLock<Vec<Arc<Node<'a>>>>,
     pub outbound: RwLock<Vec<Arc<Node<'a>>>>,
     pub ts_node: Arc<tree_sitter::Node<'a>>,
+    // for CALL and ARGUMENT nodes
+    pub invocation: Option<Invocation>,
+    // the value is cleaned by a sanitizer, taint does not go through it
+    pub sanitized: bool,
     // pub parent: Option<Arc<Container<'a>>>,
 }
 
 
-impl Node<'_> {
-    pub fn print(&self, indent: Option<usize>) {
+impl<'a> Node<'a> {
+    pub fn new(name: String, kind: NodeKind, type_name: Option<String>, ts_node: tree_sitter::Node<'a>) -> Self {
+        Node {
+            name: Some(name),
+            kind,
+            type_name,
+            inbound: RwLock::new(vec![]),
+            outbound: RwLock::new(vec![]),
+            ts_node: Arc::new(ts_node),
+            invocation: None,
+            sanitized: false,
+        }
+    }
+
+    /// Returns the line and column of the node in the source code, starting at 1.
+    pub fn get_position(&self) -> (usize, usize) {
+        let position = self.ts_node.start_position();
+        (position.row + 1, position.column + 1)
+    }
+
+    pub fn print(&self, out: &mut dyn Write, indent: Option<usize>) -> std::io::Result<()> {
         let name = self.name.clone().unwrap_or("<no name>".to_string());
         let indent = indent.unwrap_or(0);
-        println!("{}[node] name={} kind={:?}", " ".repeat(indent), name, self.kind);
-        &self.inbound.read().unwrap().iter().for_each(|i| {
-            println!("{} <- name={} kind={:?}", " ".repeat(indent + PRINT_INDENTATION), i.name.clone().unwrap_or("no name".to_string()), self.kind)
-        });
+        match self.sanitized {
+            true => writeln!(out, "{}[node] name={} kind={:?} sanitized", " ".repeat(indent), name, self.kind)?,
+            false => writeln!(out, "{}[node] name={} kind={:?}", " ".repeat(indent), name, self.kind)?,
+        }
+        for i in self.inbound.read().unwrap().iter() {
+            writeln!(out, "{} <- name={} kind={:?}", " ".repeat(indent + PRINT_INDENTATION), i.name.clone().unwrap_or("no name".to_string()), i.kind)?;
+        }
         let outbound = &self.outbound.read().unwrap();
-        outbound.iter().for_each(|o| {
-            println!("{} -> name={} kind={:?}", " ".repeat(indent + PRINT_INDENTATION), o.name.clone().unwrap_or("no name".to_string()), self.kind)
-        });
+        for o in outbound.iter() {
+            writeln!(out, "{} -> name={} kind={:?}", " ".repeat(indent + PRINT_INDENTATION), o.name.clone().unwrap_or("no name".to_string()), o.kind)?;
+        }
+        Ok(())
     }
 }
 
+/// The graph of one or several files, with a FILE container for each file.
+#[derive(Default)]
 pub struct DataFlow<'a> {
     pub containers: Vec<Arc<Container<'a>>>,
     pub ts_node_to_df_node: HashMap<tree_sitter::Node<'a>, Node<'a>>,
 }
 
 
-impl DataFlow<'_> {
-    pub fn print_graph(&self) {
+/// A node with its file, the name of its enclosing function (empty outside of functions)
+/// and the fully qualified name of its enclosing type.
+pub struct FunctionNode<'a> {
+    pub node: Arc<Node<'a>>,
+    pub file: String,
+    pub function: String,
+    pub type_name: Option<String>,
+}
+
+fn collect_function_nodes<'a>(container: &Container<'a>, file: &str, function: &str, type_name: &Option<String>, package: &Option<String>, res: &mut Vec<FunctionNode<'a>>) {
+    let function = match container.kind {
+        ContainerKind::FUNCTION | ContainerKind::CONSTRUCTOR | ContainerKind::INITIALIZER => container.name.clone().unwrap_or_default(),
+        ContainerKind::FILE => function.to_string(),
+        // the fields of a local class are not part of the enclosing function
+        _ => String::new(),
+    };
+    let type_name = match container.kind {
+        ContainerKind::FUNCTION | ContainerKind::CONSTRUCTOR | ContainerKind::INITIALIZER | ContainerKind::FILE => type_name.clone(),
+        _ => container.get_full_type_name(package),
+    };
+
+    for node in &container.nodes {
+        res.push(FunctionNode {
+            node: node.clone(),
+            file: file.to_string(),
+            function: function.clone(),
+            type_name: type_name.clone(),
+        });
+    }
+    for child in &container.containers {
+        collect_function_nodes(child, file, &function, &type_name, package, res);
+    }
+}
+
+impl<'a> DataFlow<'a> {
+    /// Add the files of another graph.
+    pub fn extend(&mut self, other: DataFlow<'a>) {
+        self.containers.extend(other.containers);
+        self.ts_node_to_df_node.extend(other.ts_node_to_df_node);
+    }
+
+    /// Returns all the nodes of all the containers with their file and function.
+    pub fn get_function_nodes(&self) -> Vec<FunctionNode<'a>> {
+        let mut res = vec![];
+        for container in &self.containers {
+            let file = container.name.clone().unwrap_or_default();
+            collect_function_nodes(container, &file, "", &None, &container.package, &mut res);
+        }
+        res
+    }
+
+    pub fn print_graph(&self, out: &mut dyn Write) -> std::io::Result<()> {
         for c in self.containers.iter() {
-            c.print(None);
+            c.print(out, None)?;
         }
+        Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,655 @@
+use crate::dataflow::common::{get_code_for_node, get_nodes_of_type, get_simple_type_name};
+use crate::dataflow::java::FileTypes;
+
+/// Types of the resources that must be closed once opened.
+const RESOURCE_TYPES: [&str; 5] = ["Connection", "Statement", "PreparedStatement", "CallableStatement", "ResultSet"];
+
+/// A resource opened in a function and not closed on all the paths.
+#[derive(Debug)]
+pub struct ResourceLeak {
+    pub name: String,
+    pub type_name: String,
+    pub function: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A local variable holding a resource.
+struct Resource<'a, 'b> {
+    name: String,
+    declarator: tree_sitter::Node<'a>,
+    // the parameters and the local variables of the function, the other variables are fields
+    locals: Vec<String>,
+    // the types of the file, to find the catch clauses catching the thrown exceptions
+    types: &'b FileTypes<'a>,
+}
+
+/// The paths leaving a statement by throwing an exception of a type, None when unknown.
+#[derive(Clone)]
+struct Thrown {
+    type_name: Option<String>,
+    open: bool,
+    closed: bool,
+}
+
+/// The states of a resource at the end of all the paths going through a statement.
+#[derive(Clone, Default)]
+struct Paths {
+    // paths that continue after the statement
+    open: bool,
+    closed: bool,
+    // paths that leave the statement by throwing an exception
+    thrown: Vec<Thrown>,
+    // paths that return from the function with the resource still open
+    returned_open: bool,
+}
+
+impl Paths {
+    fn continuing(open: bool, closed: bool) -> Paths {
+        Paths {
+            open,
+            closed,
+            ..Default::default()
+        }
+    }
+
+    fn union(&self, other: &Paths) -> Paths {
+        Paths {
+            open: self.open || other.open,
+            closed: self.closed || other.closed,
+            thrown: self.thrown.iter().chain(&other.thrown).cloned().collect(),
+            returned_open: self.returned_open || other.returned_open,
+        }
+    }
+
+    fn leaks(&self) -> bool {
+        self.open || self.thrown.iter().any(|t| t.open) || self.returned_open
+    }
+}
+
+fn is_null(node: Option<tree_sitter::Node>) -> bool {
+    node.is_none_or(|n| n.grammar_name() == "null_literal")
+}
+
+/// Returns true if the statement gives a new value to the resource (its declaration
+/// with an initial value or an assignment), null excepted.
+fn opens(node: tree_sitter::Node, resource: &Resource, code: &str) -> bool {
+    if get_nodes_of_type(node, "variable_declarator").contains(&resource.declarator) {
+        return !is_null(resource.declarator.child_by_field_name("value"));
+    }
+
+    get_nodes_of_type(node, "assignment_expression").iter().any(|assignment| {
+        assignment.child_by_field_name("left").is_some_and(|left| left.grammar_name() == "identifier" && get_code_for_node(left, code) == resource.name)
+            && !is_null(assignment.child_by_field_name("right"))
+    })
+}
+
+fn is_resource(node: tree_sitter::Node, resource: &Resource, code: &str) -> bool {
+    node.grammar_name() == "identifier" && get_code_for_node(node, code) == resource.name
+}
+
+/// Returns true if the resource is an argument of a constructor in the node (e.g. a
+/// wrapper), the created object is then responsible for closing it.
+fn is_given_to_constructor(node: tree_sitter::Node, resource: &Resource, code: &str) -> bool {
+    get_nodes_of_type(node, "argument_list").iter().any(|arguments| {
+        let mut cursor = arguments.walk();
+        let passed = arguments.named_children(&mut cursor).any(|a| is_resource(a, resource, code));
+        passed && arguments.parent().is_some_and(|p| p.grammar_name() == "object_creation_expression" || p.grammar_name() == "explicit_constructor_invocation")
+    })
+}
+
+/// Returns true if the statement closes the resource or hands it off to an object that is
+/// then responsible for closing it: a field storing it or a constructor receiving it. The
+/// methods receiving it as an argument only use it (e.g. to print the rows of a result set).
+fn closes(node: tree_sitter::Node, resource: &Resource, code: &str) -> bool {
+    let closed = get_nodes_of_type(node, "method_invocation").iter().any(|invocation| {
+        invocation.child_by_field_name("object").is_some_and(|o| is_resource(o, resource, code))
+            && invocation.child_by_field_name("name").is_some_and(|n| get_code_for_node(n, code) == "close")
+    });
+
+    let is_field = |left: tree_sitter::Node| match left.grammar_name() {
+        "field_access" => true,
+        "identifier" => !resource.locals.contains(&get_code_for_node(left, code)),
+        _ => false,
+    };
+    let stored = get_nodes_of_type(node, "assignment_expression").iter().any(|assignment| {
+        assignment.child_by_field_name("right").is_some_and(|r| is_resource(r, resource, code)) && assignment.child_by_field_name("left").is_some_and(is_field)
+    });
+
+    closed || stored || is_given_to_constructor(node, resource, code)
+}
+
+/// Returns the value of a null check of the resource in a condition: `Some(true)`
+/// for `r != null`, `Some(false)` for `r == null`.
+fn null_check(condition: tree_sitter::Node, resource: &Resource, code: &str) -> Option<bool> {
+    let condition_code: String = get_code_for_node(condition, code).chars().filter(|c| !c.is_whitespace() && *c != '(' && *c != ')').collect();
+    let not_null = [format!("{}!=null", resource.name), format!("null!={}", resource.name)];
+    let null = [format!("{}==null", resource.name), format!("null=={}", resource.name)];
+
+    if not_null.contains(&condition_code) {
+        return Some(true);
+    }
+    if null.contains(&condition_code) {
+        return Some(false);
+    }
+    None
+}
+
+fn analyze_statements<'a>(statements: impl Iterator<Item = tree_sitter::Node<'a>>, input: Paths, resource: &Resource, code: &str) -> Paths {
+    let mut result = input;
+
+    for statement in statements {
+        // the rest of the statements are not reachable
+        if !result.open && !result.closed {
+            break;
+        }
+        let paths = analyze_statement(statement, Paths::continuing(result.open, result.closed), resource, code);
+        result.thrown.extend(paths.thrown);
+        result = Paths {
+            open: paths.open,
+            closed: paths.closed,
+            thrown: result.thrown,
+            returned_open: result.returned_open || paths.returned_open,
+        };
+    }
+
+    result
+}
+
+/// Returns the types caught by a catch clause, as written in the code.
+fn get_catch_types(catch_clause: tree_sitter::Node, code: &str) -> Vec<String> {
+    get_nodes_of_type(catch_clause, "catch_type")
+        .iter()
+        .flat_map(|catch_type| {
+            let mut cursor = catch_type.walk();
+            catch_type.named_children(&mut cursor).map(|t| get_code_for_node(t, code)).collect::<Vec<String>>()
+        })
+        .collect()
+}
+
+fn analyze_try_statement(node: tree_sitter::Node, input: Paths, resource: &Resource, code: &str) -> Paths {
+    let body = node.child_by_field_name("body").map_or(input.clone(), |b| analyze_statement(b, input.clone(), resource, code));
+    let mut cursor = node.walk();
+    let catch_clauses: Vec<tree_sitter::Node> = node.named_children(&mut cursor).filter(|c| c.grammar_name() == "catch_clause").collect();
+    let finally_clause = node.named_children(&mut cursor).find(|c| c.grammar_name() == "finally_clause");
+
+    let mut result = Paths { thrown: vec![], ..body.clone() };
+    // the exceptions thrown in the try block go to the first catch clause catching their type,
+    // the exceptions of an unknown type may be caught by any of them
+    let (unknown, mut uncaught): (Vec<Thrown>, Vec<Thrown>) = body.thrown.iter().cloned().partition(|t| t.type_name.is_none());
+    for catch_clause in &catch_clauses {
+        let catch_types = get_catch_types(*catch_clause, code);
+        let (mut caught, rest): (Vec<Thrown>, Vec<Thrown>) = uncaught.into_iter().partition(|t| {
+            let thrown_type = t.type_name.as_deref().unwrap_or_default();
+            catch_types.iter().any(|c| resource.types.catches(c, thrown_type))
+        });
+        uncaught = rest;
+        caught.extend(unknown.iter().cloned());
+        // an exception may also be thrown by any statement of the try block
+        let input = Paths::continuing(
+            input.open || body.open || caught.iter().any(|t| t.open),
+            input.closed || body.closed || caught.iter().any(|t| t.closed),
+        );
+        if input.open || input.closed {
+            let catch_body = catch_clause.child_by_field_name("body").map_or(input.clone(), |b| analyze_statement(b, input, resource, code));
+            result = result.union(&catch_body);
+        }
+    }
+    if catch_clauses.is_empty() {
+        uncaught.extend(unknown);
+    }
+    result.thrown.extend(uncaught);
+
+    if let Some(finally_clause) = finally_clause {
+        let mut finally_cursor = finally_clause.walk();
+        let statements = finally_clause.named_children(&mut finally_cursor);
+        let finally = analyze_statements(statements, Paths::continuing(true, false), resource, code);
+        // the finally block is executed on all the paths: when it closes the resource, it is closed on all of them
+        if !finally.leaks() {
+            return Paths {
+                open: false,
+                closed: result.open || result.closed,
+                thrown: result.thrown.into_iter().map(|t| Thrown { open: false, closed: t.open || t.closed, ..t }).collect(),
+                returned_open: false,
+            };
+        }
+    }
+
+    result
+}
+
+/// Returns the states of the resource after a statement, given its states before it.
+/// Outside of try blocks, only the explicit exits (return and throw statements)
+/// are considered, not the exceptions thrown by the invoked methods.
+fn analyze_statement(node: tree_sitter::Node, input: Paths, resource: &Resource, code: &str) -> Paths {
+    match node.grammar_name() {
+        "block" | "constructor_body" => {
+            let mut cursor = node.walk();
+            let statements = node.named_children(&mut cursor);
+            analyze_statements(statements, input, resource, code)
+        }
+        "return_statement" => {
+            // returning the resource (or an object wrapping it) makes the caller responsible for closing it
+            let returned = node.named_child(0).is_some_and(|value| is_resource(value, resource, code) || is_given_to_constructor(value, resource, code));
+            Paths {
+                returned_open: input.open && !returned,
+                ..Default::default()
+            }
+        }
+        "throw_statement" => {
+            let type_name = node
+                .named_child(0)
+                .filter(|e| e.grammar_name() == "object_creation_expression")
+                .and_then(|e| e.child_by_field_name("type"))
+                .map(|t| get_code_for_node(t, code));
+            Paths {
+                thrown: vec![Thrown {
+                    type_name,
+                    open: input.open,
+                    closed: input.closed,
+                }],
+                ..Default::default()
+            }
+        }
+        "if_statement" => {
+            let check = node.child_by_field_name("condition").and_then(|c| null_check(c, resource, code));
+            // a null resource does not need to be closed
+            let when_true = if check == Some(false) { Paths::continuing(false, input.open || input.closed) } else { input.clone() };
+            let when_false = if check == Some(true) { Paths::continuing(false, input.open || input.closed) } else { input };
+            let consequence = node.child_by_field_name("consequence").map_or(when_true.clone(), |c| analyze_statement(c, when_true, resource, code));
+            let alternative = node.child_by_field_name("alternative").map_or(when_false.clone(), |a| analyze_statement(a, when_false, resource, code));
+            consequence.union(&alternative)
+        }
+        // the body of a loop may not be executed
+        "while_statement" | "for_statement" | "enhanced_for_statement" => match node.child_by_field_name("body") {
+            Some(body) => analyze_statement(body, input.clone(), resource, code).union(&input),
+            None => input,
+        },
+        "do_statement" => match node.child_by_field_name("body") {
+            Some(body) => analyze_statement(body, input, resource, code),
+            None => input,
+        },
+        "try_statement" | "try_with_resources_statement" => analyze_try_statement(node, input, resource, code),
+        // the statements of the groups (`case a:`) and of the rules (`case a ->`) of the switch,
+        // the nested switches are analyzed with their statements
+        "switch_expression" => {
+            let mut result = Paths::default();
+            let mut has_default = false;
+            if let Some(body) = node.child_by_field_name("body") {
+                let mut cursor = body.walk();
+                for case in body.named_children(&mut cursor) {
+                    let mut case_cursor = case.walk();
+                    let (labels, statements): (Vec<tree_sitter::Node>, Vec<tree_sitter::Node>) = case.named_children(&mut case_cursor).partition(|c| c.grammar_name() == "switch_label");
+                    has_default |= labels.iter().any(|l| get_code_for_node(*l, code).starts_with("default"));
+                    result = result.union(&analyze_statements(statements.into_iter(), input.clone(), resource, code));
+                }
+            }
+            // without a default case, no case may match
+            if !has_default {
+                result = result.union(&input);
+            }
+            result
+        }
+        "labeled_statement" | "synchronized_statement" => match node.named_child(node.named_child_count().saturating_sub(1)) {
+            Some(body) => analyze_statement(body, input, resource, code),
+            None => input,
+        },
+        _ => {
+            if opens(node, resource, code) {
+                Paths::continuing(true, false)
+            } else if closes(node, resource, code) {
+                Paths::continuing(false, true)
+            } else {
+                input
+            }
+        }
+    }
+}
+
+/// Returns the name and the body of a function: a method, a constructor, a lambda
+/// with a block or an initializer block. None for the other nodes.
+fn get_function<'a>(node: tree_sitter::Node<'a>, code: &str) -> Option<(String, tree_sitter::Node<'a>)> {
+    match node.grammar_name() {
+        "method_declaration" | "constructor_declaration" => {
+            let name = node.child_by_field_name("name").map(|n| get_code_for_node(n, code)).unwrap_or_default();
+            node.child_by_field_name("body").map(|body| (name, body))
+        }
+        "lambda_expression" => node.child_by_field_name("body").filter(|b| b.grammar_name() == "block").map(|body| ("<lambda>".to_string(), body)),
+        "static_initializer" => node.named_child(0).map(|body| ("<clinit>".to_string(), body)),
+        "block" if node.parent().is_some_and(|p| p.grammar_name() == "class_body") => Some(("<instinit>".to_string(), node)),
+        _ => None,
+    }
+}
+
+/// Returns the names of the parameters and the local variables of a function.
+fn get_local_names(function: tree_sitter::Node, code: &str) -> Vec<String> {
+    let mut names = vec![];
+    for grammar_name in ["formal_parameter", "catch_formal_parameter", "variable_declarator", "enhanced_for_statement", "resource"] {
+        names.extend(get_nodes_of_type(function, grammar_name).iter().filter_map(|n| n.child_by_field_name("name")).map(|n| get_code_for_node(n, code)));
+    }
+    // the parameters of a lambda may have no type
+    if let Some(parameters) = function.child_by_field_name("parameters").filter(|_| function.grammar_name() == "lambda_expression") {
+        names.extend(get_nodes_of_type(parameters, "identifier").iter().map(|n| get_code_for_node(*n, code)));
+    }
+    names
+}
+
+/// Find all the resources declared as local variables in a function and not
+/// closed on all its paths. The resources declared in a try-with-resources
+/// statement are closed automatically and not reported.
+fn find_function_leaks<'a>(function: tree_sitter::Node<'a>, code: &str, types: &FileTypes<'a>) -> Vec<ResourceLeak> {
+    let mut leaks = vec![];
+    let Some((function_name, body)) = get_function(function, code) else {
+        return leaks;
+    };
+    let locals = get_local_names(function, code);
+
+    for declaration in get_nodes_of_type(body, "local_variable_declaration") {
+        // the declarations of the nested functions (e.g. lambdas) are analyzed with them
+        let enclosing_function = std::iter::successors(declaration.parent(), |n| n.parent()).find(|n| get_function(*n, code).is_some());
+        if enclosing_function != Some(function) {
+            continue;
+        }
+
+        let type_name = declaration.child_by_field_name("type").map(|t| get_simple_type_name(&get_code_for_node(t, code))).unwrap_or_default();
+        if !RESOURCE_TYPES.contains(&type_name.as_str()) {
+            continue;
+        }
+
+        let mut cursor = declaration.walk();
+        for declarator in declaration.children_by_field_name("declarator", &mut cursor) {
+            if let Some(name) = declarator.child_by_field_name("name") {
+                let resource = Resource {
+                    name: get_code_for_node(name, code),
+                    declarator,
+                    locals: locals.clone(),
+                    types,
+                };
+
+                // before its declaration, there is nothing to close
+                let paths = analyze_statement(body, Paths::continuing(false, true), &resource, code);
+                if paths.leaks() {
+                    leaks.push(ResourceLeak {
+                        name: resource.name,
+                        type_name: type_name.clone(),
+                        function: function_name.clone(),
+                        line: name.start_position().row + 1,
+                        column: name.start_position().column + 1,
+                    });
+                }
+            }
+        }
+    }
+
+    leaks
+}
+
+/// Find all the resources (connections, statements and result sets) opened
+/// and not closed on all the paths of the functions below a node: the methods,
+/// the constructors, the lambdas and the initializer blocks. The types of the file tell
+/// which catch clauses catch the exceptions thrown while a resource is open.
+pub fn find_resource_leaks<'a>(node: tree_sitter::Node<'a>, code: &str, types: &FileTypes<'a>) -> Vec<ResourceLeak> {
+    let mut functions = vec![];
+    for grammar_name in ["method_declaration", "constructor_declaration", "lambda_expression", "static_initializer", "block"] {
+        functions.extend(get_nodes_of_type(node, grammar_name).into_iter().filter(|f| get_function(*f, code).is_some()));
+    }
+
+    functions.into_iter().flat_map(|f| find_function_leaks(f, code, types)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::dataflow::testing::{bundled_library, parse_java};
+
+    fn find_leaks(code: &str) -> Vec<ResourceLeak> {
+        let tree = parse_java(code);
+        let types = FileTypes::new(&tree, code, bundled_library(), Arc::default());
+        find_resource_leaks(tree.root_node(), code, &types)
+    }
+
+    #[test]
+    fn reports_a_connection_not_closed() {
+        let leaks = find_leaks(
+            r#"
+class Test {
+    void query(DataSource dataSource) throws SQLException {
+        Connection connection = dataSource.getConnection();
+        connection.createStatement();
+    }
+}
+"#,
+        );
+
+        assert_eq!(leaks.len(), 1);
+        assert_eq!((leaks[0].function.as_str(), leaks[0].name.as_str(), leaks[0].type_name.as_str()), ("query", "connection", "Connection"));
+        assert_eq!((leaks[0].line, leaks[0].column), (4, 20));
+    }
+
+    #[test]
+    fn does_not_report_the_resources_of_a_try_with_resources() {
+        let leaks = find_leaks(
+            r#"
+class Test {
+    void query(DataSource dataSource) throws SQLException {
+        try (Connection connection = dataSource.getConnection()) {
+            connection.createStatement();
+        }
+    }
+}
+"#,
+        );
+
+        assert!(leaks.is_empty());
+    }
+
+    #[test]
+    fn does_not_report_a_resource_closed_in_a_finally_block() {
+        let leaks = find_leaks(
+            r#"
+class Test {
+    void query(DataSource dataSource) throws SQLException {
+        Connection connection = null;
+        try {
+            connection = dataSource.getConnection();
+            connection.createStatement();
+        } finally {
+            if (connection != null) {
+                connection.close();
+            }
+        }
+    }
+}
+"#,
+        );
+
+        assert!(leaks.is_empty());
+    }
+
+    #[test]
+    fn reports_a_resource_not_closed_on_an_early_exit() {
+        let leaks = find_leaks(
+            r#"
+class Test {
+    void thrown(DataSource dataSource, String sql) throws SQLException {
+        Connection connection = dataSource.getConnection();
+        if (sql.isEmpty()) {
+            throw new IllegalArgumentException("empty");
+        }
+        connection.close();
+    }
+
+    void returned(DataSource dataSource, String sql) throws SQLException {
+        Statement statement = dataSource.getConnection().createStatement();
+        if (sql.isEmpty()) {
+            return;
+        }
+        statement.close();
+    }
+}
+"#,
+        );
+
+        let names: Vec<&str> = leaks.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["connection", "statement"]);
+    }
+
+    #[test]
+    fn a_returned_resource_is_closed_by_the_caller() {
+        let leaks = find_leaks(
+            r#"
+class Test {
+    Connection open(DataSource dataSource) throws SQLException {
+        Connection connection = dataSource.getConnection();
+        return connection;
+    }
+}
+"#,
+        );
+
+        assert!(leaks.is_empty());
+    }
+
+    #[test]
+    fn a_resource_given_to_a_method_is_not_closed_by_it() {
+        let code = std::fs::read_to_string("testdata/sqli/Test1.java").unwrap();
+        let leaks = find_leaks(&code);
+
+        // the result set is printed by DatabaseHelper.printResults, none of them is closed
+        let leaks: Vec<(&str, usize)> = leaks.iter().map(|l| (l.name.as_str(), l.line)).collect();
+        assert_eq!(leaks, vec![("connection", 55), ("statement", 57), ("rs", 58)]);
+    }
+
+    #[test]
+    fn a_resource_stored_in_a_field_or_wrapped_is_closed_by_its_owner() {
+        let leaks = find_leaks(
+            r#"
+class Test {
+    Connection connection;
+
+    Test(DataSource dataSource) throws SQLException {
+        Connection opened = dataSource.getConnection();
+        connection = opened;
+    }
+
+    Repository open(DataSource dataSource) throws SQLException {
+        Connection connection = dataSource.getConnection();
+        return new Repository(connection);
+    }
+
+    void log(DataSource dataSource) throws SQLException {
+        Connection other = dataSource.getConnection();
+        Connection copy = other;
+        System.out.println(copy);
+    }
+}
+"#,
+        );
+
+        // a copy in a local variable is not a field
+        let names: Vec<&str> = leaks.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["other", "copy"]);
+    }
+
+    #[test]
+    fn analyzes_the_cases_of_a_switch_with_their_own_statements() {
+        let leaks = find_leaks(
+            r#"
+class Test {
+    void nested(DataSource dataSource, int kind, int mode) throws SQLException {
+        Connection connection = dataSource.getConnection();
+        switch (kind) {
+            case 1:
+                connection.close();
+                switch (mode) {
+                    case 1:
+                        return;
+                }
+                break;
+            default:
+                connection.close();
+        }
+    }
+
+    void rules(DataSource dataSource, int kind) throws SQLException {
+        Statement statement = dataSource.getConnection().createStatement();
+        switch (kind) {
+            case 1 -> statement.close();
+            case 2 -> {
+                return;
+            }
+            default -> statement.close();
+        }
+    }
+}
+"#,
+        );
+
+        // the return of the nested switch is after the close, the rule returns before it
+        let names: Vec<&str> = leaks.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["statement"]);
+    }
+
+    #[test]
+    fn an_exception_is_only_caught_by_the_catch_clauses_of_its_type() {
+        let leaks = find_leaks(
+            r#"
+import java.sql.*;
+
+class Test {
+    void caught(DataSource dataSource, String sql) throws SQLException {
+        Connection connection = dataSource.getConnection();
+        try {
+            if (sql.isEmpty()) {
+                throw new IllegalArgumentException("empty");
+            }
+        } catch (RuntimeException e) {
+            connection.close();
+            return;
+        }
+        connection.close();
+    }
+
+    void uncaught(DataSource dataSource, String sql) throws SQLException {
+        Connection other = dataSource.getConnection();
+        try {
+            if (sql.isEmpty()) {
+                throw new IllegalArgumentException("empty");
+            }
+        } catch (SQLException e) {
+            other.close();
+            return;
+        }
+        other.close();
+    }
+}
+"#,
+        );
+
+        let names: Vec<&str> = leaks.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["other"]);
+    }
+
+    #[test]
+    fn reports_the_leaks_of_the_lambdas_and_the_initializers() {
+        let leaks = find_leaks(
+            r#"
+class Test {
+    static {
+        Connection connection = DriverManager.getConnection("jdbc:h2:mem:");
+    }
+
+    Runnable task = () -> {
+        Statement statement = CONNECTION.createStatement();
+    };
+}
+"#,
+        );
+
+        let leaks: Vec<(&str, &str)> = leaks.iter().map(|l| (l.function.as_str(), l.name.as_str())).collect();
+        assert_eq!(leaks, vec![("<lambda>", "statement"), ("<clinit>", "connection")]);
+    }
+}
+
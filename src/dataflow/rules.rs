@@ -0,0 +1,419 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::dataflow::common::get_simple_type_name;
+use crate::dataflow::errors::DataflowError;
+use crate::dataflow::hierarchy::TypeHierarchy;
+use crate::dataflow::library::{Endpoint, Flow, Library, MethodSummary};
+use crate::dataflow::model::{FunctionNode, Invocation};
+
+/// Sources, sinks and sanitizers of the JDK, servlet and common encoding APIs, bundled with the binaries.
+const BUNDLED_RULES: &str = include_str!("rules.json");
+/// The supertype of all the exceptions, a catch clause with several types catches one of its subtypes.
+const THROWABLE_TYPE: &str = "java.lang.Throwable";
+
+/// A fully qualified method signature, written `com.example.Helper.escape(java.lang.String)`.
+/// Without a parameter list, it designates all the overloads of the method.
+/// Constructors are named `<init>`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct MethodSignature {
+    pub type_name: String,
+    pub method: String,
+    pub parameters: Option<Vec<String>>,
+}
+
+impl TryFrom<String> for MethodSignature {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let (name, parameters) = match value.split_once('(') {
+            Some((name, rest)) => {
+                let list = rest.trim().strip_suffix(')').ok_or_else(|| anyhow!("invalid method signature {}, missing )", value))?;
+                let parameters = list.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect();
+                (name.trim(), Some(parameters))
+            }
+            None => (value.trim(), None),
+        };
+        let (type_name, method) = name.rsplit_once('.').ok_or_else(|| anyhow!("invalid method signature {}, expected <type>.<method>", value))?;
+
+        Ok(MethodSignature {
+            type_name: type_name.to_string(),
+            method: method.to_string(),
+            parameters,
+        })
+    }
+}
+
+impl MethodSignature {
+    /// Returns true if a call invokes the method, declared by the type of the receiver or one of its supertypes.
+    /// With parameters, the call has the same number of arguments and the arguments of a known type
+    /// can be given to the parameters (e.g. `String.valueOf(char[])` but not `String.valueOf(int)`).
+    pub fn matches(&self, invocation: &Invocation, library: &Library) -> bool {
+        if invocation.method != self.method {
+            return false;
+        }
+        if self.parameters.as_ref().is_some_and(|p| !library.accepts(p, &invocation.argument_types)) {
+            return false;
+        }
+        invocation.type_name.as_ref().is_some_and(|t| library.is_subtype(t, &self.type_name))
+    }
+}
+
+/// Values that are not trusted: the values returned by a method, the parameters
+/// of a given type (e.g. the request of a servlet), or the exceptions caught by
+/// the catch clauses (their details must not be shown to the users).
+#[derive(Debug, Deserialize)]
+pub struct SourceRule {
+    pub id: String,
+    pub method: Option<MethodSignature>,
+    // fully qualified type of the parameters
+    pub parameter_type: Option<String>,
+    // names of the methods declaring the parameters, all the methods when empty
+    #[serde(default)]
+    pub methods: Vec<String>,
+    // fully qualified type the methods declaring the parameters belong to, directly or
+    // through a subtype (e.g. the servlets for javax.servlet.http.HttpServlet)
+    pub declaring_type: Option<String>,
+    // fully qualified type of the exceptions caught by the catch clauses, directly or through a subtype
+    pub caught_type: Option<String>,
+    // kinds of the tainted values (e.g. header or cookie), the id of the rule when empty
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl SourceRule {
+    /// Returns the labels of the values from the source.
+    pub fn get_labels(&self) -> Vec<String> {
+        match self.labels.is_empty() {
+            true => vec![self.id.clone()],
+            false => self.labels.clone(),
+        }
+    }
+
+    /// Returns true if a parameter of a function is a source.
+    pub fn matches_parameter(&self, parameter: &FunctionNode, hierarchy: &TypeHierarchy, library: &Library) -> bool {
+        let type_matches = match (&self.parameter_type, &parameter.node.type_name) {
+            (Some(expected), Some(actual)) => expected == actual || (!actual.contains('.') && get_simple_type_name(expected) == *actual),
+            _ => false,
+        };
+        let method_matches = self.methods.is_empty() || self.methods.contains(&parameter.function);
+        let declaring_type_matches = match (&self.declaring_type, &parameter.type_name) {
+            (Some(expected), Some(actual)) => hierarchy.is_subtype(actual, expected) || library.is_subtype(actual, expected),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        type_matches && method_matches && declaring_type_matches
+    }
+
+    /// Returns true if a variable of a function is the parameter of a catch clause that is a source.
+    pub fn matches_catch_parameter(&self, variable: &FunctionNode, hierarchy: &TypeHierarchy, library: &Library) -> bool {
+        let is_catch_parameter = variable.node.ts_node.parent().is_some_and(|p| p.grammar_name() == "catch_formal_parameter");
+        match (&self.caught_type, &variable.node.type_name) {
+            (Some(expected), _) if is_catch_parameter && expected == THROWABLE_TYPE => true,
+            (Some(expected), Some(actual)) if is_catch_parameter => hierarchy.is_subtype(actual, expected) || library.is_subtype(actual, expected),
+            _ => false,
+        }
+    }
+}
+
+/// Arguments of a method that must not receive values from the sources. The id of
+/// the rule is the vulnerability (e.g. sql-injection), shared by several rules.
+#[derive(Debug, Deserialize)]
+pub struct SinkRule {
+    pub id: String,
+    pub method: MethodSignature,
+    // indexes of the arguments, all the arguments when empty
+    #[serde(default)]
+    pub arguments: Vec<usize>,
+    // labels of the values reported by the sink, all the labels when empty
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl SinkRule {
+    pub fn matches(&self, invocation: &Invocation, library: &Library) -> bool {
+        let argument_matches = invocation.argument.is_some_and(|a| self.arguments.is_empty() || self.arguments.contains(&a));
+        argument_matches && self.method.matches(invocation, library)
+    }
+
+    pub fn accepts(&self, label: &str) -> bool {
+        self.labels.is_empty() || self.labels.iter().any(|l| l == label)
+    }
+}
+
+/// A method whose values are clean for some vulnerabilities, e.g. a value encoded for SQL
+/// is safe for a SQL injection but not for a cross-site scripting.
+#[derive(Debug, Deserialize)]
+pub struct SanitizerRule {
+    pub method: MethodSignature,
+    // the vulnerabilities (ids of the sink rules), all of them when empty
+    #[serde(default)]
+    pub vulnerabilities: Vec<String>,
+    // values cleaned by the method
+    #[serde(default = "default_sanitized_endpoints")]
+    pub endpoints: Vec<Endpoint>,
+}
+
+fn default_sanitized_endpoints() -> Vec<Endpoint> {
+    vec![Endpoint::Return]
+}
+
+impl SanitizerRule {
+    /// Returns true if the value of a call (returned value or argument) is clean for a vulnerability.
+    pub fn sanitizes(&self, invocation: &Invocation, vulnerability: &str, library: &Library) -> bool {
+        let endpoint_matches = self.endpoints.iter().any(|e| match (e, invocation.argument) {
+            (Endpoint::Return, None) => true,
+            (Endpoint::Argument(index), Some(argument)) => *index == argument,
+            (Endpoint::Arguments, Some(_)) => true,
+            _ => false,
+        });
+        let vulnerability_matches = self.vulnerabilities.is_empty() || self.vulnerabilities.iter().any(|v| v == vulnerability);
+        endpoint_matches && vulnerability_matches && self.method.matches(invocation, library)
+    }
+}
+
+/// How the values flow through a method of the analyzed code base (e.g. a helper
+/// of another project), written like the summaries of the library.
+#[derive(Debug, Deserialize)]
+pub struct SummaryRule {
+    pub method: MethodSignature,
+    pub returns: Option<String>,
+    #[serde(default)]
+    pub flows: Vec<Flow>,
+    #[serde(default)]
+    pub sanitizes: Vec<Endpoint>,
+}
+
+impl From<&SummaryRule> for MethodSummary {
+    fn from(rule: &SummaryRule) -> Self {
+        MethodSummary {
+            type_name: rule.method.type_name.clone(),
+            method: rule.method.method.clone(),
+            parameters: rule.method.parameters.clone(),
+            returns: rule.returns.clone(),
+            flows: rule.flows.clone(),
+            sanitizes: rule.sanitizes.clone(),
+        }
+    }
+}
+
+/// The rules of a scan, loaded from JSON files.
+#[derive(Debug, Default, Deserialize)]
+pub struct RuleSet {
+    #[serde(default)]
+    pub sources: Vec<SourceRule>,
+    #[serde(default)]
+    pub sinks: Vec<SinkRule>,
+    #[serde(default)]
+    pub sanitizers: Vec<SanitizerRule>,
+    #[serde(default)]
+    pub summaries: Vec<SummaryRule>,
+}
+
+impl RuleSet {
+    /// Returns the vulnerabilities of the sinks, without duplicates.
+    pub fn get_vulnerabilities(&self) -> Vec<&String> {
+        let mut vulnerabilities: Vec<&String> = vec![];
+        for sink in &self.sinks {
+            if !vulnerabilities.contains(&&sink.id) {
+                vulnerabilities.push(&sink.id);
+            }
+        }
+        vulnerabilities
+    }
+
+    /// Returns true if the value of a call is clean for a vulnerability according to the sanitizers.
+    pub fn is_sanitized(&self, invocation: &Invocation, vulnerability: &str, library: &Library) -> bool {
+        self.sanitizers.iter().any(|s| s.sanitizes(invocation, vulnerability, library))
+    }
+
+    /// Returns the bundled rules.
+    pub fn bundled() -> Result<RuleSet> {
+        Ok(serde_json::from_str(BUNDLED_RULES)?)
+    }
+
+    pub fn from_file(path: &str) -> Result<RuleSet, DataflowError> {
+        let content = std::fs::read_to_string(path).map_err(|source| DataflowError::Io { path: path.to_string(), source })?;
+        serde_json::from_str(&content).map_err(|source| DataflowError::Rules { path: path.to_string(), source })
+    }
+
+    /// Add the rules of another rule set.
+    pub fn extend(&mut self, other: RuleSet) {
+        self.sources.extend(other.sources);
+        self.sinks.extend(other.sinks);
+        self.sanitizers.extend(other.sanitizers);
+        self.summaries.extend(other.summaries);
+    }
+
+    /// Add the summaries of the rules to a library, replacing the summaries of the library
+    /// for the same methods. The types of the sources, sinks and sanitizers are also added,
+    /// so that the types of the calls are resolved.
+    pub fn add_to(&self, library: &mut Library) {
+        for summary in &self.summaries {
+            library.add_summary(MethodSummary::from(summary));
+        }
+        let methods = self
+            .sources
+            .iter()
+            .filter_map(|s| s.method.as_ref())
+            .chain(self.sinks.iter().map(|s| &s.method))
+            .chain(self.sanitizers.iter().map(|s| &s.method));
+        for method in methods {
+            library.add_type(&method.type_name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::model::CallKind;
+    use crate::dataflow::testing::{scan_java, TestFinding};
+
+    fn invocation(type_name: &str, method: &str, arguments: usize, argument: Option<usize>) -> Invocation {
+        Invocation {
+            type_name: Some(type_name.to_string()),
+            method: method.to_string(),
+            arguments,
+            argument_types: vec![None; arguments],
+            argument,
+            kind: CallKind::VIRTUAL,
+        }
+    }
+
+    #[test]
+    fn parses_the_method_signatures() {
+        let signature = MethodSignature::try_from("com.acme.Codec.wrap(java.lang.String, int)".to_string()).unwrap();
+
+        assert_eq!((signature.type_name.as_str(), signature.method.as_str()), ("com.acme.Codec", "wrap"));
+        assert_eq!(signature.parameters, Some(vec!["java.lang.String".to_string(), "int".to_string()]));
+        assert_eq!(MethodSignature::try_from("com.acme.Codec.wrap".to_string()).unwrap().parameters, None);
+        assert!(MethodSignature::try_from("wrap(".to_string()).is_err());
+        assert!(MethodSignature::try_from("wrap".to_string()).is_err());
+    }
+
+    #[test]
+    fn a_signature_with_parameters_matches_the_types_of_the_arguments() {
+        let library = Library::bundled().unwrap();
+        let signature = MethodSignature::try_from("java.lang.String.valueOf(char[])".to_string()).unwrap();
+        let value_of = |argument_type: Option<&str>| Invocation {
+            argument_types: vec![argument_type.map(String::from)],
+            ..invocation("java.lang.String", "valueOf", 1, None)
+        };
+
+        assert!(signature.matches(&value_of(Some("char[]")), &library));
+        assert!(!signature.matches(&value_of(Some("java.lang.Object")), &library));
+        // without the type of the argument, the number of arguments
+        assert!(signature.matches(&value_of(None), &library));
+        assert!(!signature.matches(&invocation("java.lang.String", "valueOf", 2, None), &library));
+    }
+
+    #[test]
+    fn a_sink_with_parameters_only_matches_the_arguments_of_their_types() {
+        let code = r#"
+import javax.servlet.http.*;
+import com.acme.Database;
+
+class Test extends HttpServlet {
+    Database database;
+
+    protected void doGet(HttpServletRequest request, HttpServletResponse response) {
+        String query = request.getParameter("query");
+        Object value = request.getParameter("value");
+        database.run(query);
+        database.run(value);
+    }
+}
+"#;
+        let rules: RuleSet = serde_json::from_str(r#"{"sinks": [{"id": "sql-injection", "method": "com.acme.Database.run(java.lang.String)"}]}"#).unwrap();
+        let mut all_rules = RuleSet::bundled().unwrap();
+        all_rules.extend(rules);
+
+        let lines: Vec<usize> = scan_java(code, &all_rules).iter().filter(|f| f.vulnerability == "sql-injection").map(|f| f.line).collect();
+        assert_eq!(lines, vec![11]);
+    }
+
+    #[test]
+    fn a_sink_matches_the_calls_of_the_subtypes_on_its_arguments() {
+        let rules = RuleSet::bundled().unwrap();
+        let library = Library::bundled().unwrap();
+        let execute_query = rules.sinks.iter().find(|s| s.method.method == "executeQuery").unwrap();
+
+        assert!(execute_query.matches(&invocation("java.sql.PreparedStatement", "executeQuery", 1, Some(0)), &library));
+        // the call itself is not an argument
+        assert!(!execute_query.matches(&invocation("java.sql.Statement", "executeQuery", 1, None), &library));
+        assert!(!execute_query.matches(&invocation("com.acme.Statement", "executeQuery", 1, Some(0)), &library));
+    }
+
+    #[test]
+    fn the_summaries_of_the_rules_are_used_for_the_methods_of_other_projects() {
+        let code = r#"
+import java.sql.*;
+import javax.servlet.http.*;
+import com.acme.Codec;
+
+class Test extends HttpServlet {
+    Statement statement;
+
+    protected void doGet(HttpServletRequest request, HttpServletResponse response) throws Exception {
+        Codec codec = new Codec();
+        statement.executeQuery(codec.wrap(request.getParameter("name")));
+        statement.executeQuery(codec.escape(request.getParameter("name")));
+    }
+}
+"#;
+        let mut rules = RuleSet::bundled().unwrap();
+        rules.extend(
+            serde_json::from_str(
+                r#"{
+  "summaries": [
+    {"method": "com.acme.Codec.wrap(java.lang.String)", "returns": "java.lang.String", "flows": ["arg0 -> return"]},
+    {"method": "com.acme.Codec.escape", "returns": "java.lang.String", "flows": ["arg0 -> return"], "sanitizes": ["arg0"]}
+  ]
+}"#,
+            )
+            .unwrap(),
+        );
+
+        let expected = TestFinding {
+            vulnerability: "sql-injection".to_string(),
+            label: "parameter".to_string(),
+            function: "doGet".to_string(),
+            line: 11,
+            path: vec!["getParameter()@11:51", "wrap()@11:38#0", "wrap()@11:38", "executeQuery()@11:19#0"].into_iter().map(String::from).collect(),
+        };
+        assert_eq!(scan_java(code, &rules), vec![expected]);
+    }
+
+    #[test]
+    fn a_sanitizer_cleans_its_endpoints_for_its_vulnerabilities() {
+        let rules = RuleSet::bundled().unwrap();
+        let library = Library::bundled().unwrap();
+        let set_string = |argument| invocation("java.sql.PreparedStatement", "setString", 2, argument);
+
+        assert!(rules.is_sanitized(&set_string(Some(1)), "sql-injection", &library));
+        assert!(!rules.is_sanitized(&set_string(Some(0)), "sql-injection", &library));
+        assert!(!rules.is_sanitized(&set_string(Some(1)), "xss", &library));
+        // without vulnerabilities, the value is clean for all of them
+        let parse_int = invocation("java.lang.Integer", "parseInt", 1, None);
+        assert!(rules.is_sanitized(&parse_int, "xss", &library));
+        assert!(rules.is_sanitized(&parse_int, "sql-injection", &library));
+    }
+
+    #[test]
+    fn the_labels_of_a_source_default_to_its_id() {
+        let rules: RuleSet = serde_json::from_str(
+            r#"{
+  "sources": [
+    {"id": "custom", "method": "com.acme.Input.read"},
+    {"id": "headers", "method": "com.acme.Input.header", "labels": ["header", "untrusted"]}
+  ]
+}"#,
+        )
+        .unwrap();
+
+        assert_eq!(rules.sources[0].get_labels(), vec!["custom"]);
+        assert_eq!(rules.sources[1].get_labels(), vec!["header", "untrusted"]);
+    }
+}
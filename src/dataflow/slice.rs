@@ -0,0 +1,273 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+
+use crate::dataflow::model::{DataFlow, Node};
+
+/// The node a slice starts from: the node at a position in the file or a node
+/// designated by its name in a function.
+#[derive(Debug)]
+pub enum SliceCriterion {
+    Position { file: Option<String>, line: usize, column: usize },
+    Name { function: String, name: String },
+}
+
+impl TryFrom<&str> for SliceCriterion {
+    type Error = anyhow::Error;
+
+    /// Parse `[file:]line:column` or `function:name`.
+    fn try_from(value: &str) -> Result<Self> {
+        let parts: Vec<&str> = value.split(':').collect();
+        let numbers: Vec<usize> = parts.iter().rev().take(2).map_while(|p| p.parse::<usize>().ok()).collect();
+
+        match (parts.len(), numbers.as_slice()) {
+            (2 | 3, [column, line]) => Ok(SliceCriterion::Position {
+                file: (parts.len() == 3).then(|| parts[0].to_string()),
+                line: *line,
+                column: *column,
+            }),
+            (2, _) => Ok(SliceCriterion::Name {
+                function: parts[0].to_string(),
+                name: parts[1].to_string(),
+            }),
+            _ => Err(anyhow!("invalid slice criterion {}, expected [file:]line:column or function:name", value)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SliceOptions {
+    // maximum number of edges between the start node and the nodes of the slice
+    pub depth: Option<usize>,
+    // name of the function the slice is limited to
+    pub container: Option<String>,
+}
+
+/// A node of a slice with its distance (number of edges) to the start node.
+pub struct SliceNode<'a> {
+    pub node: Arc<Node<'a>>,
+    pub file: String,
+    pub function: String,
+    pub depth: usize,
+}
+
+/// A statement of a slice with the file containing it.
+pub struct SliceStatement<'a> {
+    pub statement: tree_sitter::Node<'a>,
+    pub file: String,
+}
+
+pub struct Slice<'a> {
+    pub nodes: Vec<SliceNode<'a>>,
+    // the statements of the nodes, by file in the order of the source code
+    pub statements: Vec<SliceStatement<'a>>,
+}
+
+/// Returns true if the path of an analyzed file ends with the file of a criterion, compared
+/// by components: `Test.java` designates `src/Test.java` but not `src/MyTest.java`.
+pub fn is_criterion_file(path: &str, file: &str) -> bool {
+    Path::new(path).ends_with(file)
+}
+
+/// Find the node of a criterion. For a position, the innermost node containing it is chosen
+/// in the files whose path ends with the file of the criterion (all the files when none).
+pub fn find_node<'a>(dataflow: &DataFlow<'a>, criterion: &SliceCriterion) -> Option<Arc<Node<'a>>> {
+    let nodes = dataflow.get_function_nodes();
+
+    match criterion {
+        SliceCriterion::Position { file, line, column } => {
+            let point = tree_sitter::Point::new(line.saturating_sub(1), column.saturating_sub(1));
+            nodes
+                .into_iter()
+                .filter(|n| file.as_ref().is_none_or(|f| is_criterion_file(&n.file, f)))
+                .map(|n| n.node)
+                .filter(|n| n.ts_node.start_position() <= point && point < n.ts_node.end_position())
+                .min_by_key(|n| n.ts_node.end_byte() - n.ts_node.start_byte())
+        }
+        SliceCriterion::Name { function, name } => nodes
+            .into_iter()
+            .find(|n| n.function == *function && n.node.name.as_ref() == Some(name))
+            .map(|n| n.node),
+    }
+}
+
+/// Returns the statement (or declaration) of the code containing a node.
+fn get_statement(node: tree_sitter::Node) -> tree_sitter::Node {
+    std::iter::successors(Some(node), |n| n.parent())
+        .find(|n| {
+            let grammar_name = n.grammar_name();
+            grammar_name.ends_with("_statement") || grammar_name.ends_with("_declaration") || grammar_name == "formal_parameter" || grammar_name == "catch_formal_parameter" || grammar_name == "resource"
+        })
+        .unwrap_or(node)
+}
+
+/// Walk the graph from a node following the edges returned by `next`, breadth first.
+fn walk_slice<'a>(dataflow: &DataFlow<'a>, start: &Arc<Node<'a>>, options: &SliceOptions, next: fn(&Node<'a>) -> Vec<Arc<Node<'a>>>) -> Slice<'a> {
+    // the file and the function of the nodes, found once for all the visited nodes
+    let locations: HashMap<*const Node<'a>, (String, String)> = dataflow.get_function_nodes().into_iter().map(|n| (Arc::as_ptr(&n.node), (n.file, n.function))).collect();
+    let get_location = |node: &Arc<Node<'a>>| locations.get(&Arc::as_ptr(node)).cloned().unwrap_or_default();
+
+    let (file, function) = get_location(start);
+    let mut nodes = vec![SliceNode {
+        node: start.clone(),
+        file,
+        function,
+        depth: 0,
+    }];
+    let mut visited: HashSet<*const Node<'a>> = HashSet::from([Arc::as_ptr(start)]);
+    let mut current = 0;
+
+    while current < nodes.len() {
+        let depth = nodes[current].depth;
+        if options.depth.is_some_and(|d| depth >= d) {
+            current += 1;
+            continue;
+        }
+        for other in next(&nodes[current].node) {
+            if !visited.insert(Arc::as_ptr(&other)) {
+                continue;
+            }
+            let (file, function) = get_location(&other);
+            if options.container.as_ref().is_some_and(|c| *c != function) {
+                continue;
+            }
+            nodes.push(SliceNode {
+                node: other,
+                file,
+                function,
+                depth: depth + 1,
+            });
+        }
+        current += 1;
+    }
+
+    let mut statements: Vec<SliceStatement<'a>> = vec![];
+    for node in &nodes {
+        let statement = get_statement(*node.node.ts_node);
+        if !statements.iter().any(|s| s.statement == statement) {
+            statements.push(SliceStatement { statement, file: node.file.clone() });
+        }
+    }
+    statements.sort_by(|a, b| a.file.cmp(&b.file).then(a.statement.start_byte().cmp(&b.statement.start_byte())));
+
+    Slice { nodes, statements }
+}
+
+/// Returns all the nodes whose values may flow into the value of a node (walking the
+/// inbound edges), with their statements.
+pub fn backward_slice<'a>(dataflow: &DataFlow<'a>, start: &Arc<Node<'a>>, options: &SliceOptions) -> Slice<'a> {
+    walk_slice(dataflow, start, options, |node| node.inbound.read().unwrap().clone())
+}
+
+/// Returns all the nodes the value of a node may flow into (walking the outbound
+/// edges), with their statements: the impact of the value.
+pub fn forward_slice<'a>(dataflow: &DataFlow<'a>, start: &Arc<Node<'a>>, options: &SliceOptions) -> Slice<'a> {
+    walk_slice(dataflow, start, options, |node| node.outbound.read().unwrap().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::java::build_project_graph;
+    use crate::dataflow::testing::{bundled_library, parse_java};
+
+    const CODE: &str = r#"
+class Test {
+    int compute(int a, int b) {
+        int sum = a + b;
+        int unrelated = b * 2;
+        int doubled = sum * 2;
+        log(unrelated);
+        return doubled;
+    }
+
+    void log(int value) {}
+}
+"#;
+
+    /// Returns the lines of the statements of the backward or forward slice of a criterion.
+    fn slice_lines(criterion: &str, forward: bool, options: &SliceOptions) -> Vec<usize> {
+        let tree = parse_java(CODE);
+        let dataflow = build_project_graph(&[("Test.java", CODE, &tree)], bundled_library());
+        let start = find_node(&dataflow, &SliceCriterion::try_from(criterion).unwrap()).unwrap();
+        let slice = match forward {
+            true => forward_slice(&dataflow, &start, options),
+            false => backward_slice(&dataflow, &start, options),
+        };
+        slice.statements.iter().map(|s| s.statement.start_position().row + 1).collect()
+    }
+
+    #[test]
+    fn parses_the_criteria() {
+        assert!(matches!(SliceCriterion::try_from("Test.java:4:13").unwrap(), SliceCriterion::Position { file: Some(_), line: 4, column: 13 }));
+        assert!(matches!(SliceCriterion::try_from("4:13").unwrap(), SliceCriterion::Position { file: None, line: 4, column: 13 }));
+        assert!(matches!(SliceCriterion::try_from("compute:sum").unwrap(), SliceCriterion::Name { .. }));
+        assert!(SliceCriterion::try_from("sum").is_err());
+    }
+
+    #[test]
+    fn the_backward_slice_contains_the_statements_the_value_depends_on() {
+        // the parameters a and b (on the same line), then sum and doubled
+        assert_eq!(slice_lines("compute:doubled", false, &SliceOptions::default()), vec![3, 3, 4, 6]);
+        assert_eq!(slice_lines("compute:unrelated", false, &SliceOptions::default()), vec![3, 5]);
+    }
+
+    #[test]
+    fn the_backward_slice_is_limited_by_the_depth() {
+        let options = SliceOptions { depth: Some(1), container: None };
+
+        assert_eq!(slice_lines("compute:doubled", false, &options), vec![4, 6]);
+    }
+
+    #[test]
+    fn a_position_designates_the_innermost_node() {
+        let tree = parse_java(CODE);
+        let dataflow = build_project_graph(&[("Test.java", CODE, &tree)], bundled_library());
+        let name = |criterion: &str| find_node(&dataflow, &SliceCriterion::try_from(criterion).unwrap()).and_then(|n| n.name.clone());
+
+        assert_eq!(name("6:13").as_deref(), Some("doubled"));
+        assert_eq!(name("3:21").as_deref(), Some("a"));
+        assert_eq!(name("2:1"), None);
+    }
+
+    #[test]
+    fn the_statements_are_in_the_file_of_their_nodes() {
+        let other = "class Other { int compute(int a, int b) { return a; } }";
+        let (other_tree, tree) = (parse_java(other), parse_java(CODE));
+        let dataflow = build_project_graph(&[("Other.java", other, &other_tree), ("Test.java", CODE, &tree)], bundled_library());
+        let start = find_node(&dataflow, &SliceCriterion::try_from("Test.java:6:13").unwrap()).unwrap();
+        let slice = backward_slice(&dataflow, &start, &SliceOptions::default());
+
+        let statements: Vec<(&str, usize)> = slice.statements.iter().map(|s| (s.file.as_str(), s.statement.start_position().row + 1)).collect();
+        assert_eq!(statements, vec![("Test.java", 3), ("Test.java", 3), ("Test.java", 4), ("Test.java", 6)]);
+    }
+
+    #[test]
+    fn the_file_of_a_position_is_matched_by_path_components() {
+        let other = "class MyTest { int compute(int a, int b) {\n\n\n\n\n    int tested = a; return tested; } }";
+        let (other_tree, tree) = (parse_java(other), parse_java(CODE));
+        let dataflow = build_project_graph(&[("src/MyTest.java", other, &other_tree), ("src/Test.java", CODE, &tree)], bundled_library());
+        let name = |criterion: &str| find_node(&dataflow, &SliceCriterion::try_from(criterion).unwrap()).and_then(|n| n.name.clone());
+
+        assert_eq!(name("Test.java:6:13").as_deref(), Some("doubled"));
+        assert_eq!(name("src/Test.java:6:13").as_deref(), Some("doubled"));
+        assert_eq!(name("MyTest.java:6:13").as_deref(), Some("tested"));
+        assert_eq!(name("est.java:6:13"), None);
+    }
+
+    #[test]
+    fn the_forward_slice_contains_the_statements_depending_on_the_value() {
+        assert_eq!(slice_lines("compute:sum", true, &SliceOptions::default()), vec![4, 6, 8]);
+        // the argument of log, not its parameter: the calls are not followed
+        assert_eq!(slice_lines("compute:unrelated", true, &SliceOptions::default()), vec![5, 7]);
+    }
+
+    #[test]
+    fn the_forward_slice_is_limited_to_a_container() {
+        let options = SliceOptions { depth: None, container: Some("log".to_string()) };
+
+        assert_eq!(slice_lines("compute:unrelated", true, &options), vec![5]);
+    }
+}
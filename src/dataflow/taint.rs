@@ -0,0 +1,1003 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use tracing::{debug, debug_span};
+
+use crate::dataflow::callgraph::{CallGraph, MethodDeclaration, MethodId};
+use crate::dataflow::library::Library;
+use crate::dataflow::model::{DataFlow, EdgeKind, FunctionNode, Node, NodeKind};
+use crate::dataflow::rules::{RuleSet, SourceRule};
+
+/// The maximum number of call sites kept in the context of the analysis.
+pub const MAX_CONTEXT_DEPTH: usize = 2;
+
+pub struct TaintOptions {
+    // number of call sites (k) remembered to return a value from a function to its call:
+    // with 0, the values returned by a function go to all its calls
+    pub context_depth: usize,
+}
+
+impl Default for TaintOptions {
+    fn default() -> Self {
+        TaintOptions {
+            context_depth: MAX_CONTEXT_DEPTH,
+        }
+    }
+}
+
+/// A value from a source reaching a sink.
+pub struct Finding<'a> {
+    pub source: Arc<Node<'a>>,
+    pub source_rule: String,
+    // label of the value, from the source rule
+    pub label: String,
+    pub sink: Arc<Node<'a>>,
+    pub sink_rule: String,
+    // file and function of the sink
+    pub file: String,
+    pub function: String,
+    // a shortest path from the source to the sink
+    pub path: Vec<PathStep<'a>>,
+}
+
+/// A node of the path of a finding, with the kind of the edge leading to it
+/// (none for the source).
+#[derive(Clone)]
+pub struct PathStep<'a> {
+    pub node: Arc<Node<'a>>,
+    pub edge: Option<EdgeKind>,
+    pub file: String,
+}
+
+/// A tainted value: a label and the source it comes from. The value of a parameter
+/// is also followed to summarize a function, with an empty label.
+#[derive(Clone)]
+struct Taint<'a> {
+    label: String,
+    source: Arc<Node<'a>>,
+    source_rule: String,
+    // the node where the value enters the current function: the source or the call of a function returning it
+    entry: Arc<Node<'a>>,
+    // the path from the source to the value returned by the callee, empty for a source of the current function
+    path: Vec<PathStep<'a>>,
+    // number of nested calls the value is returned from, at most the context depth + 1
+    depth: usize,
+}
+
+impl PartialEq for Taint<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.label == other.label && Arc::ptr_eq(&self.source, &other.source) && self.depth == other.depth
+    }
+}
+
+impl<'a> Taint<'a> {
+    fn parameter(parameter: &Arc<Node<'a>>) -> Taint<'a> {
+        Taint {
+            label: String::new(),
+            source: parameter.clone(),
+            source_rule: String::new(),
+            entry: parameter.clone(),
+            path: vec![],
+            depth: 0,
+        }
+    }
+}
+
+/// A sink reached by the value of a parameter of a function, in the function or in its callees.
+#[derive(Clone)]
+struct ParameterSink<'a> {
+    parameter: usize,
+    sink: Arc<Node<'a>>,
+    // the path from the parameter to the sink
+    path: Vec<PathStep<'a>>,
+}
+
+// the path is only an example of the flow, there are many through recursive calls
+impl PartialEq for ParameterSink<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.parameter == other.parameter && Arc::ptr_eq(&self.sink, &other.sink)
+    }
+}
+
+/// How the values flow through a function of the analyzed code, for a vulnerability.
+#[derive(Clone, Default)]
+struct FunctionSummary<'a> {
+    // indexes of the parameters whose values are returned, with the number of call sites
+    // to remember to return them to the right call (this call and the nested calls)
+    returns: Vec<(usize, usize)>,
+    // the values of the sources of the function or of its callees that are returned
+    returned_taints: Vec<Taint<'a>>,
+    sinks: Vec<ParameterSink<'a>>,
+    // the nodes of other functions receiving the values of the parameters, with the path from
+    // the parameter: fields, and calls receiving values returned out of the context
+    escapes: Vec<(usize, Arc<Node<'a>>, Vec<PathStep<'a>>)>,
+}
+
+// the summaries are compared by value to find the fixpoint: the same flows, in any order,
+// with the nodes compared by identity and without their paths
+impl PartialEq for FunctionSummary<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        let escapes = |s: &Self| s.escapes.iter().map(|(p, n, _)| (*p, Arc::as_ptr(n))).collect::<Vec<_>>();
+        same_elements(&self.returns, &other.returns)
+            && same_elements(&self.returned_taints, &other.returned_taints)
+            && same_elements(&self.sinks, &other.sinks)
+            && same_elements(&escapes(self), &escapes(other))
+    }
+}
+
+/// Returns true if two lists have the same elements, in any order.
+fn same_elements<T: PartialEq>(a: &[T], b: &[T]) -> bool {
+    a.len() == b.len() && a.iter().all(|x| b.contains(x)) && b.iter().all(|x| a.contains(x))
+}
+
+/// The nodes reached by the taints and the sinks of the callees reached by the arguments.
+struct Propagation<'a> {
+    tainted: Vec<(Arc<Node<'a>>, Vec<Taint<'a>>)>,
+    callee_sinks: Vec<(Arc<Node<'a>>, Taint<'a>, ParameterSink<'a>)>,
+}
+
+type NodeKey<'a> = *const Node<'a>;
+
+/// Append a path to another one, the first node of the appended path being reached with an edge kind.
+/// A node ending the first path and starting the appended one is kept once.
+fn concat_paths<'a>(mut path: Vec<PathStep<'a>>, mut next: Vec<PathStep<'a>>, edge: EdgeKind) -> Vec<PathStep<'a>> {
+    match (path.last(), next.first_mut()) {
+        (Some(last), Some(first)) if Arc::ptr_eq(&last.node, &first.node) => {
+            next.remove(0);
+        }
+        (Some(_), Some(first)) => first.edge = Some(edge),
+        _ => {}
+    }
+    path.append(&mut next);
+    path
+}
+
+/// The project being analyzed, with the nodes of all its files and its resolved calls.
+struct Analysis<'a, 'r> {
+    rules: &'r RuleSet,
+    library: &'r Library,
+    call_graph: &'r CallGraph<'a>,
+    context_depth: usize,
+    // the nodes of all the files
+    nodes: Vec<FunctionNode<'a>>,
+    // index of each node in nodes
+    positions: HashMap<NodeKey<'a>, usize>,
+    // the calls of the analyzed code with their targets
+    calls: HashMap<NodeKey<'a>, &'r Vec<MethodId>>,
+    // the arguments of the calls of the analyzed code, with their call and index
+    arguments: HashMap<NodeKey<'a>, (Arc<Node<'a>>, usize)>,
+}
+
+impl<'a, 'r> Analysis<'a, 'r> {
+    fn new(dataflow: &DataFlow<'a>, call_graph: &'r CallGraph<'a>, rules: &'r RuleSet, library: &'r Library, options: &TaintOptions) -> Analysis<'a, 'r> {
+        let nodes = dataflow.get_function_nodes();
+        let positions = nodes.iter().enumerate().map(|(i, n)| (Arc::as_ptr(&n.node), i)).collect();
+
+        let mut calls = HashMap::new();
+        let mut arguments = HashMap::new();
+        for (call, targets) in &call_graph.calls {
+            calls.insert(Arc::as_ptr(&call.node), targets);
+            for argument in call.node.inbound.read().unwrap().iter() {
+                let call_name = argument.name.as_ref().and_then(|n| n.rsplit_once('#')).map(|(c, _)| c);
+                let index = argument.invocation.as_ref().and_then(|i| i.argument);
+                if let (NodeKind::ARGUMENT, true, Some(index)) = (&argument.kind, call_name == call.node.name.as_deref(), index) {
+                    arguments.insert(Arc::as_ptr(argument), (call.node.clone(), index));
+                }
+            }
+        }
+
+        Analysis {
+            rules,
+            library,
+            call_graph,
+            context_depth: options.context_depth,
+            nodes,
+            positions,
+            calls,
+            arguments,
+        }
+    }
+
+    /// Returns the targets of a call when they all are summarized, i.e. implemented by the analyzed code.
+    fn get_targets(&self, call: &Arc<Node<'a>>) -> Option<&'r Vec<MethodId>> {
+        let targets = self.calls.get(&Arc::as_ptr(call))?;
+        targets.iter().all(|t| self.call_graph.get_method(t).is_some_and(|m| !m.is_abstract)).then_some(*targets)
+    }
+
+    /// Returns the file and the function of a node.
+    fn get_location(&self, node: &Arc<Node<'a>>) -> (String, String) {
+        match self.positions.get(&Arc::as_ptr(node)) {
+            Some(position) => (self.nodes[*position].file.clone(), self.nodes[*position].function.clone()),
+            None => (String::new(), String::new()),
+        }
+    }
+
+    /// Returns the taints of the values of a node from the source rules matching it.
+    fn get_source_taints(&self, node: &FunctionNode<'a>) -> Vec<Taint<'a>> {
+        let matches = |rule: &&SourceRule| match node.node.kind {
+            NodeKind::PARAMETER => rule.matches_parameter(node, &self.call_graph.hierarchy, self.library),
+            NodeKind::VARIABLE => rule.matches_catch_parameter(node, &self.call_graph.hierarchy, self.library),
+            NodeKind::CALL => rule.method.as_ref().zip(node.node.invocation.as_ref()).is_some_and(|(m, i)| m.matches(i, self.library)),
+            _ => false,
+        };
+
+        let mut taints = vec![];
+        for rule in self.rules.sources.iter().filter(matches) {
+            for label in rule.get_labels() {
+                let taint = Taint {
+                    label,
+                    source: node.node.clone(),
+                    source_rule: rule.id.clone(),
+                    entry: node.node.clone(),
+                    path: vec![],
+                    depth: 0,
+                };
+                if !taints.contains(&taint) {
+                    taints.push(taint);
+                }
+            }
+        }
+        taints
+    }
+
+    /// Returns the tainted nodes among some nodes: the sources and the calls of functions
+    /// returning the values of their sources.
+    fn get_sources<'n>(&self, nodes: impl Iterator<Item = &'n FunctionNode<'a>>, summaries: &HashMap<MethodId, FunctionSummary<'a>>) -> Vec<(Arc<Node<'a>>, Vec<Taint<'a>>)>
+    where
+        'a: 'n,
+    {
+        let mut sources = vec![];
+        for node in nodes {
+            let mut taints = self.get_source_taints(node);
+            for target in self.get_targets(&node.node).into_iter().flatten() {
+                for returned in summaries.get(target).iter().flat_map(|s| s.returned_taints.iter()) {
+                    let taint = Taint {
+                        entry: node.node.clone(),
+                        ..returned.clone()
+                    };
+                    if !taints.contains(&taint) {
+                        taints.push(taint);
+                    }
+                }
+            }
+            if !taints.is_empty() {
+                sources.push((node.node.clone(), taints));
+            }
+        }
+        sources
+    }
+
+    /// Returns true if a node is a sink of a vulnerability for the values with a label (any label when none).
+    fn is_sink(&self, node: &Node, vulnerability: &str, label: Option<&str>) -> bool {
+        match (&node.kind, &node.invocation) {
+            (NodeKind::ARGUMENT, Some(invocation)) => self
+                .rules
+                .sinks
+                .iter()
+                .any(|r| r.id == vulnerability && label.is_none_or(|l| r.accepts(l)) && r.matches(invocation, self.library)),
+            _ => false,
+        }
+    }
+
+    /// Returns true if the value of a node is clean for a vulnerability, either sanitized by the
+    /// summary of the invoked method or by a sanitizer.
+    fn is_sanitized(&self, node: &Node, vulnerability: &str) -> bool {
+        node.sanitized || node.invocation.as_ref().is_some_and(|i| self.rules.is_sanitized(i, vulnerability, self.library))
+    }
+
+    /// Propagate the taints of the sources through the outbound edges and returns the taints
+    /// of all the reached nodes. The taints do not go further than a node that is clean
+    /// for the vulnerability, or than a node outside of the given nodes.
+    ///
+    /// The arguments of the calls of the analyzed code follow the summaries of the invoked
+    /// functions instead of the edges of the call. A value returned through more nested calls
+    /// than the context depth is returned to all the calls of the function.
+    fn propagate(&self, sources: Vec<(Arc<Node<'a>>, Vec<Taint<'a>>)>, vulnerability: &str, summaries: &HashMap<MethodId, FunctionSummary<'a>>, within: Option<&HashSet<NodeKey<'a>>>) -> Propagation<'a> {
+        let mut propagation = Propagation {
+            tainted: vec![],
+            callee_sinks: vec![],
+        };
+        let mut indexes: HashMap<NodeKey<'a>, usize> = HashMap::new();
+        let mut to_visit = sources;
+
+        while let Some((node, taints)) = to_visit.pop() {
+            let index = *indexes.entry(Arc::as_ptr(&node)).or_insert_with(|| {
+                propagation.tainted.push((node.clone(), vec![]));
+                propagation.tainted.len() - 1
+            });
+            let new_taints: Vec<Taint<'a>> = taints.into_iter().filter(|t| !propagation.tainted[index].1.contains(t)).collect();
+            if new_taints.is_empty() {
+                continue;
+            }
+            propagation.tainted[index].1.extend(new_taints.iter().cloned());
+
+            if self.is_sanitized(&node, vulnerability) || within.is_some_and(|w| !w.contains(&Arc::as_ptr(&node))) {
+                continue;
+            }
+
+            let summarized_call = self.arguments.get(&Arc::as_ptr(&node)).and_then(|(call, argument)| Some((call, *argument, self.get_targets(call)?)));
+            if let Some((call, argument, targets)) = summarized_call {
+                for (target, summary) in targets.iter().filter_map(|t| summaries.get(t).map(|s| (t, s))) {
+                    for (_, depth) in summary.returns.iter().filter(|(p, _)| *p == argument) {
+                        let taints: Vec<Taint<'a>> = new_taints
+                            .iter()
+                            .map(|t| Taint {
+                                depth: t.depth.max(*depth),
+                                ..t.clone()
+                            })
+                            .collect();
+                        if *depth <= self.context_depth {
+                            to_visit.push((call.clone(), taints));
+                            continue;
+                        }
+                        // the call is not in the context anymore
+                        for other in self.call_graph.get_callers(target) {
+                            let taints = taints
+                                .iter()
+                                .map(|t| Taint {
+                                    entry: other.node.clone(),
+                                    path: self.get_taint_path(t, &node, vulnerability),
+                                    ..t.clone()
+                                })
+                                .collect();
+                            to_visit.push((other.node.clone(), taints));
+                        }
+                    }
+                    // e.g. the values stored into a field enter the functions reading it
+                    for (_, escape, escape_path) in summary.escapes.iter().filter(|(p, _, _)| *p == argument) {
+                        let taints = new_taints
+                            .iter()
+                            .map(|t| Taint {
+                                entry: escape.clone(),
+                                path: concat_paths(self.get_taint_path(t, &node, vulnerability), escape_path.clone(), EdgeKind::ARGUMENT),
+                                ..t.clone()
+                            })
+                            .collect();
+                        to_visit.push((escape.clone(), taints));
+                    }
+                    for sink in summary.sinks.iter().filter(|s| s.parameter == argument) {
+                        for taint in &new_taints {
+                            propagation.callee_sinks.push((node.clone(), taint.clone(), sink.clone()));
+                        }
+                    }
+                }
+                continue;
+            }
+
+            for next in node.outbound.read().unwrap().iter() {
+                to_visit.push((next.clone(), new_taints.clone()));
+            }
+        }
+
+        propagation
+    }
+
+    /// Returns a shortest path from a node to another one, walking the inbound edges back from
+    /// the last node. The intermediate nodes of the path are not clean for the vulnerability.
+    fn get_path(&self, first: &Arc<Node<'a>>, last: &Arc<Node<'a>>, vulnerability: &str) -> Vec<PathStep<'a>> {
+        // each visited node with the index of the next node towards the last node
+        let mut visited: Vec<(Arc<Node<'a>>, Option<usize>)> = vec![(last.clone(), None)];
+        let mut current = 0;
+
+        while current < visited.len() {
+            let node = visited[current].0.clone();
+            if Arc::ptr_eq(&node, first) {
+                let mut path = vec![];
+                let mut index = Some(current);
+                while let Some(i) = index {
+                    let (step, next) = &visited[i];
+                    path.push(PathStep {
+                        node: step.clone(),
+                        edge: path.last().map(|previous: &PathStep| EdgeKind::between(&previous.node, step)),
+                        file: self.get_location(step).0,
+                    });
+                    index = *next;
+                }
+                return path;
+            }
+
+            for previous in node.inbound.read().unwrap().iter() {
+                let is_visited = visited.iter().any(|(n, _)| Arc::ptr_eq(n, previous));
+                if !is_visited && (Arc::ptr_eq(previous, first) || !self.is_sanitized(previous, vulnerability)) {
+                    visited.push((previous.clone(), Some(current)));
+                }
+            }
+            current += 1;
+        }
+
+        vec![]
+    }
+
+    /// Returns the path of a taint from its source to a node of the current function.
+    fn get_taint_path(&self, taint: &Taint<'a>, node: &Arc<Node<'a>>, vulnerability: &str) -> Vec<PathStep<'a>> {
+        concat_paths(taint.path.clone(), self.get_path(&taint.entry, node, vulnerability), EdgeKind::RETURN)
+    }
+
+    /// Compute the summary of a function with the summaries of its callees: where the values of
+    /// its parameters go and the values of the sources it returns.
+    fn summarize(&self, method: &MethodDeclaration<'a>, vulnerability: &str, summaries: &HashMap<MethodId, FunctionSummary<'a>>) -> FunctionSummary<'a> {
+        let within: HashSet<NodeKey<'a>> = method.container.nodes.iter().map(Arc::as_ptr).collect();
+        let parameters = method.container.nodes.iter().filter(|n| matches!(n.kind, NodeKind::PARAMETER));
+        let mut summary = FunctionSummary::default();
+
+        for (index, parameter) in parameters.enumerate() {
+            let propagation = self.propagate(vec![(parameter.clone(), vec![Taint::parameter(parameter)])], vulnerability, summaries, Some(&within));
+            let mut sinks = vec![];
+            for (node, taints) in &propagation.tainted {
+                if !within.contains(&Arc::as_ptr(node)) {
+                    summary.escapes.push((index, node.clone(), self.get_taint_path(&taints[0], node, vulnerability)));
+                    continue;
+                }
+                match node.kind {
+                    NodeKind::RETURN => {
+                        for taint in taints {
+                            let depth = (taint.depth + 1).min(self.context_depth + 1);
+                            if !summary.returns.contains(&(index, depth)) {
+                                summary.returns.push((index, depth));
+                            }
+                        }
+                    }
+                    NodeKind::ARGUMENT if self.is_sink(node, vulnerability, None) => sinks.push((node.clone(), self.get_path(parameter, node, vulnerability))),
+                    _ => {}
+                }
+            }
+            for (argument, _, sink) in &propagation.callee_sinks {
+                let path = concat_paths(self.get_path(parameter, argument, vulnerability), sink.path.clone(), EdgeKind::ARGUMENT);
+                sinks.push((sink.sink.clone(), path));
+            }
+            for (sink, path) in sinks {
+                if !summary.sinks.iter().any(|s| s.parameter == index && Arc::ptr_eq(&s.sink, &sink)) {
+                    summary.sinks.push(ParameterSink { parameter: index, sink, path });
+                }
+            }
+        }
+
+        let returned = method.container.nodes.iter().find(|n| matches!(n.kind, NodeKind::RETURN));
+        if let Some(returned) = returned {
+            let nodes = self.nodes.iter().filter(|n| within.contains(&Arc::as_ptr(&n.node)));
+            let sources = self.get_sources(nodes, summaries);
+            let propagation = self.propagate(sources, vulnerability, summaries, Some(&within));
+            let taints = propagation.tainted.iter().filter(|(n, _)| Arc::ptr_eq(n, returned)).flat_map(|(_, taints)| taints.iter());
+            for taint in taints {
+                summary.returned_taints.push(Taint {
+                    path: self.get_taint_path(taint, returned, vulnerability),
+                    ..taint.clone()
+                });
+            }
+        }
+
+        summary
+    }
+
+    /// Compute the summaries of all the functions of the analyzed code. The summaries of the
+    /// callers of a function are computed again when its summary changes, until a fixpoint
+    /// is reached for the recursive functions.
+    fn summarize_all(&self, vulnerability: &str) -> HashMap<MethodId, FunctionSummary<'a>> {
+        let mut summaries: HashMap<MethodId, FunctionSummary<'a>> = HashMap::new();
+        let mut to_visit: Vec<&MethodId> = self.call_graph.methods.iter().filter(|m| !m.is_abstract).map(|m| &m.id).collect();
+
+        while let Some(id) = to_visit.pop() {
+            let Some(method) = self.call_graph.get_method(id) else {
+                continue;
+            };
+            let summary = self.summarize(method, vulnerability, &summaries);
+            if summaries.get(id).map_or(summary == FunctionSummary::default(), |s| *s == summary) {
+                continue;
+            }
+            summaries.insert(id.clone(), summary);
+            for caller in self.call_graph.get_callers(id) {
+                if !to_visit.contains(&&caller.caller) {
+                    to_visit.push(&caller.caller);
+                }
+            }
+        }
+
+        summaries
+    }
+}
+
+/// Find all the values from the sources of the rules that flow into their sinks, one finding
+/// for each label of the values. The propagation is done for each vulnerability, so that
+/// a value cleaned by a sanitizer of one vulnerability is still tainted for the others.
+///
+/// The values flow between the functions of the files through the resolved calls of the
+/// call graph: each function is summarized once (parameters flowing into the returned value,
+/// into sinks or into fields) and its summary is used at each of its calls, up to the
+/// context depth of the options.
+pub fn find_tainted_flows<'a>(dataflow: &DataFlow<'a>, call_graph: &CallGraph<'a>, rules: &RuleSet, library: &Library, options: &TaintOptions) -> Vec<Finding<'a>> {
+    let analysis = Analysis::new(dataflow, call_graph, rules, library, options);
+    let mut findings: Vec<Finding<'a>> = vec![];
+
+    for vulnerability in rules.get_vulnerabilities() {
+        let _span = debug_span!("vulnerability", name = vulnerability.as_str()).entered();
+        let summaries = analysis.summarize_all(vulnerability);
+        debug!(summaries = summaries.len(), "methods summarized");
+        let sources = analysis.get_sources(analysis.nodes.iter(), &summaries);
+        let propagation = analysis.propagate(sources, vulnerability, &summaries, None);
+
+        let mut reached = vec![];
+        for (node, taints) in propagation.tainted {
+            for taint in taints {
+                if analysis.is_sink(&node, vulnerability, Some(&taint.label)) {
+                    reached.push((taint, node.clone(), None));
+                }
+            }
+        }
+        for (argument, taint, sink) in propagation.callee_sinks {
+            if analysis.is_sink(&sink.sink, vulnerability, Some(&taint.label)) {
+                reached.push((taint, sink.sink.clone(), Some((argument, sink.path))));
+            }
+        }
+
+        for (taint, sink, callee) in reached {
+            let exists = |f: &&Finding<'a>| f.sink_rule == *vulnerability && Arc::ptr_eq(&f.source, &taint.source) && Arc::ptr_eq(&f.sink, &sink);
+            if findings.iter().any(|f| exists(&f) && f.label == taint.label) {
+                continue;
+            }
+            // the path is computed once for each source and sink
+            let path = match (findings.iter().find(exists), callee) {
+                (Some(finding), _) => finding.path.clone(),
+                (None, Some((argument, sink_path))) => concat_paths(analysis.get_taint_path(&taint, &argument, vulnerability), sink_path, EdgeKind::ARGUMENT),
+                (None, None) => analysis.get_taint_path(&taint, &sink, vulnerability),
+            };
+            let (file, function) = analysis.get_location(&sink);
+            findings.push(Finding {
+                path,
+                source: taint.source,
+                source_rule: taint.source_rule,
+                label: taint.label,
+                sink,
+                sink_rule: vulnerability.clone(),
+                file,
+                function,
+            });
+        }
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::callgraph::build_call_graph;
+    use crate::dataflow::java::build_project_graph;
+    use crate::dataflow::testing::{parse_java, scan_java, scan_project, TestFinding};
+
+    #[test]
+    fn a_parameter_of_the_request_flows_into_a_sink() {
+        let code = r#"
+import java.sql.*;
+import javax.servlet.http.*;
+
+class Test extends HttpServlet {
+    Statement statement;
+
+    protected void doPost(HttpServletRequest request, HttpServletResponse response) throws Exception {
+        String name = request.getParameter("name");
+        String query = "select * from users where name = '" + name + "'";
+        statement.executeQuery(query);
+        statement.executeQuery("select 1");
+    }
+}
+"#;
+        let findings = scan_java(code, &RuleSet::bundled().unwrap());
+
+        let expected = TestFinding {
+            vulnerability: "sql-injection".to_string(),
+            label: "parameter".to_string(),
+            function: "doPost".to_string(),
+            line: 11,
+            path: vec!["getParameter()@9:31", "name", "query", "executeQuery()@11:19#0"].into_iter().map(String::from).collect(),
+        };
+        assert_eq!(findings, vec![expected]);
+    }
+
+    #[test]
+    fn only_the_arguments_of_the_rules_are_sinks() {
+        let code = r#"
+import java.sql.*;
+import javax.servlet.http.*;
+
+class Test extends HttpServlet {
+    protected void doGet(HttpServletRequest request, HttpServletResponse response) throws Exception {
+        String url = request.getParameter("url");
+        response.sendRedirect(url);
+        response.setHeader(url, "no-cache");
+    }
+}
+"#;
+        let findings = scan_java(code, &RuleSet::bundled().unwrap());
+
+        assert_eq!(findings.iter().map(|f| (f.vulnerability.as_str(), f.line)).collect::<Vec<_>>(), vec![("open-redirect", 8)]);
+    }
+
+    /// Returns a servlet whose doGet method has a body, starting at line 10.
+    fn servlet(body: &str) -> String {
+        format!(
+            r#"
+import java.sql.*;
+import javax.servlet.http.*;
+import org.owasp.esapi.ESAPI;
+
+class Test extends HttpServlet {{
+    Statement statement;
+
+    protected void doGet(HttpServletRequest request, HttpServletResponse response) throws Exception {{
+{}
+    }}
+}}
+"#,
+            body
+        )
+    }
+
+    fn scan_servlet(body: &str) -> Vec<(String, usize)> {
+        scan_java(&servlet(body), &RuleSet::bundled().unwrap()).into_iter().map(|f| (f.vulnerability, f.line)).collect()
+    }
+
+    #[test]
+    fn a_sanitized_value_is_clean_for_its_vulnerability_only() {
+        let findings = scan_servlet(
+            r#"
+        String name = ESAPI.encoder().encodeForSQL(null, request.getParameter("name"));
+        statement.executeQuery(name);
+        response.getWriter().println(name);
+"#,
+        );
+
+        assert_eq!(findings, vec![("xss".to_string(), 13)]);
+    }
+
+    #[test]
+    fn a_value_parsed_as_a_number_is_clean_for_all_the_vulnerabilities() {
+        let findings = scan_servlet(
+            r#"
+        int id = Integer.parseInt(request.getParameter("id"));
+        statement.executeQuery("select * from users where id = " + id);
+        response.getWriter().println(id);
+        String raw = request.getParameter("id").trim();
+        statement.executeQuery("select * from users where id = " + raw);
+"#,
+        );
+
+        assert_eq!(findings, vec![("sql-injection".to_string(), 15)]);
+    }
+
+    #[test]
+    fn a_call_on_a_type_is_not_an_access_to_the_elements_of_a_collection() {
+        let code = r#"
+import java.io.*;
+import java.nio.file.*;
+import javax.servlet.http.*;
+
+class Test extends HttpServlet {
+    protected void doGet(HttpServletRequest request, HttpServletResponse response) throws Exception {
+        Path path = Paths.get("/var/uploads", request.getParameter("name"));
+        Files.readAllLines(path);
+    }
+}
+"#;
+        let findings: Vec<(String, usize)> = scan_java(code, &RuleSet::bundled().unwrap()).into_iter().map(|f| (f.vulnerability, f.line)).collect();
+
+        // Paths.get is the sink, not a read of the elements of a collection named Paths
+        assert_eq!(findings, vec![("path-traversal".to_string(), 8)]);
+    }
+
+    #[test]
+    fn the_summaries_of_recursive_functions_reach_a_fixpoint() {
+        let findings = scan_servlet(
+            r#"
+        statement.executeQuery(clean(request.getParameter("id"), 3));
+        statement.executeQuery(first(request.getParameter("id"), 3));
+    }
+
+    String clean(String value, int depth) {
+        if (depth == 0) {
+            return value.trim();
+        }
+        return clean(value.substring(1), depth - 1);
+    }
+
+    // the value is only returned through the other function
+    String first(String value, int depth) {
+        if (depth == 0) {
+            return "";
+        }
+        return second(value, depth - 1);
+    }
+
+    String second(String value, int depth) {
+        if (depth == 0) {
+            return value;
+        }
+        return first(value, depth - 1);
+"#,
+        );
+
+        let mut lines: Vec<usize> = findings.into_iter().map(|(_, line)| line).collect();
+        lines.sort();
+        assert_eq!(lines, vec![11, 12]);
+    }
+
+    #[test]
+    fn reports_the_details_of_a_caught_exception_written_to_the_response() {
+        let code = servlet(
+            r#"
+        try {
+            Integer.parseInt(request.getParameter("n"));
+        } catch (NumberFormatException e) {
+            response.getWriter().println(e.getMessage());
+            response.getWriter().println("invalid number");
+        }
+"#,
+        );
+        let findings: Vec<(String, String, usize)> = scan_java(&code, &RuleSet::bundled().unwrap()).into_iter().map(|f| (f.vulnerability, f.label, f.line)).collect();
+
+        // the message of an exception is not a cross-site scripting, the generic message is not reported
+        assert_eq!(findings, vec![("information-exposure".to_string(), "exception".to_string(), 14)]);
+    }
+
+    #[test]
+    fn reports_a_finding_for_each_label() {
+        let findings = scan_java(
+            &servlet(
+                r#"
+        String user = request.getHeader("X-User") + request.getCookies()[0].getValue();
+        statement.executeQuery(user);
+"#,
+            ),
+            &RuleSet::bundled().unwrap(),
+        );
+        let mut labels: Vec<String> = findings.into_iter().map(|f| f.label).collect();
+        labels.sort();
+
+        assert_eq!(labels, vec!["cookie", "header"]);
+    }
+
+    #[test]
+    fn a_sink_only_reports_its_labels() {
+        let findings = scan_java(
+            &servlet(
+                r#"
+        String content = java.nio.file.Files.readString(java.nio.file.Path.of("/etc/motd"));
+        response.setHeader("X-Motd", content);
+        response.setHeader("X-User", request.getHeader("X-User"));
+        response.getWriter().println(content);
+"#,
+            ),
+            &RuleSet::bundled().unwrap(),
+        );
+        let reported: Vec<(&str, &str, usize)> = findings.iter().map(|f| (f.vulnerability.as_str(), f.label.as_str(), f.line)).collect();
+
+        // the content of a file is not a header injection
+        assert!(reported.contains(&("header-injection", "header", 13)));
+        assert!(reported.contains(&("xss", "file", 14)));
+        assert!(!reported.contains(&("header-injection", "file", 12)));
+    }
+
+    #[test]
+    fn reports_the_shortest_path_once_for_a_source_and_a_sink() {
+        let findings = scan_java(
+            &servlet(
+                r#"
+        String id = request.getParameter("id");
+        String copy = id;
+        String other = copy;
+        statement.executeQuery(other + id);
+"#,
+            ),
+            &RuleSet::bundled().unwrap(),
+        );
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].path, vec!["getParameter()@11:29", "id", "executeQuery()@14:19#0"]);
+    }
+
+    #[test]
+    fn the_steps_of_a_path_have_the_kinds_of_their_edges() {
+        let code = servlet(
+            r#"
+        StringBuilder query = new StringBuilder();
+        query.append(request.getParameter("id"));
+        statement.executeQuery(query.toString());
+"#,
+        );
+        let rules = RuleSet::bundled().unwrap();
+        let mut library = Library::bundled().unwrap();
+        rules.add_to(&mut library);
+        let tree = parse_java(&code);
+        let dataflow = build_project_graph(&[("Test.java", &code, &tree)], &library);
+        let call_graph = build_call_graph(&dataflow);
+        let findings = find_tainted_flows(&dataflow, &call_graph, &rules, &library, &TaintOptions::default());
+
+        let edges: Vec<Option<EdgeKind>> = findings[0].path.iter().map(|s| s.edge).collect();
+        // getParameter() -> append(#0) -> query -> toString() -> executeQuery(#0)
+        assert_eq!(edges, vec![None, Some(EdgeKind::ARGUMENT), Some(EdgeKind::SIDE_EFFECT), Some(EdgeKind::RETURN), Some(EdgeKind::ARGUMENT)]);
+    }
+
+    #[test]
+    fn a_parameter_source_is_scoped_by_the_type_declaring_the_method() {
+        let code = r#"
+import java.sql.*;
+import javax.servlet.http.HttpServlet;
+import javax.servlet.http.HttpServletRequest;
+import javax.servlet.http.HttpServletResponse;
+
+abstract class BaseServlet extends HttpServlet {}
+
+class Test extends BaseServlet {
+    Statement statement;
+
+    protected void doGet(HttpServletRequest request, HttpServletResponse response) throws Exception {
+        statement.executeQuery("select " + request.getQueryString());
+    }
+}
+
+class Helper {
+    Statement statement;
+
+    void doGet(HttpServletRequest request) throws Exception {
+        statement.executeQuery("select " + request.getQueryString());
+    }
+}
+"#;
+        let mut rules = RuleSet::bundled().unwrap();
+        rules.sources.clear();
+        rules.extend(RuleSet::from_file("testdata/rules/servlet-requests.json").unwrap());
+        // the bundled sinks only accept the labels of the bundled sources
+        rules.extend(serde_json::from_str(r#"{"sinks": [{"id": "sql-injection", "method": "java.sql.Statement.executeQuery", "labels": ["request"]}]}"#).unwrap());
+
+        let findings: Vec<(String, usize)> = scan_java(code, &rules).into_iter().map(|f| (f.label, f.line)).collect();
+        // the servlet inherits HttpServlet through a class of the project, the helper does not
+        assert_eq!(findings, vec![("request".to_string(), 13)]);
+    }
+
+    const DAO: &str = r#"
+package com.example.dao;
+
+import java.sql.*;
+
+public class UserDao {
+    Statement statement;
+
+    public void find(String id) throws SQLException {
+        statement.executeQuery("select * from users where id = " + id);
+    }
+
+    public void findAll(String order) throws SQLException {
+        statement.executeQuery("select * from users");
+    }
+}
+"#;
+
+    fn controller(call: &str) -> String {
+        format!(
+            r#"
+package com.example.web;
+
+import com.example.dao.UserDao;
+import javax.servlet.http.*;
+
+public class UserServlet extends HttpServlet {{
+    UserDao dao = new UserDao();
+
+    protected void doGet(HttpServletRequest request, HttpServletResponse response) throws Exception {{
+        {}
+    }}
+}}
+"#,
+            call
+        )
+    }
+
+    #[test]
+    fn a_value_flows_into_a_sink_of_a_method_of_another_file() {
+        let controller = controller(r#"dao.find(request.getParameter("id"));"#);
+        let findings = scan_project(&[("UserServlet.java", &controller), ("UserDao.java", DAO)], &RuleSet::bundled().unwrap(), &TaintOptions::default());
+
+        // the sink is reported in the called method, the path goes through its parameter
+        assert_eq!(findings.len(), 1);
+        assert_eq!((findings[0].function.as_str(), findings[0].line), ("find", 10));
+        assert!(findings[0].path.contains(&"id".to_string()));
+    }
+
+    #[test]
+    fn a_parameter_that_does_not_reach_a_sink_is_not_reported() {
+        let controller = controller(r#"dao.findAll(request.getParameter("order"));"#);
+
+        assert!(scan_project(&[("UserServlet.java", &controller), ("UserDao.java", DAO)], &RuleSet::bundled().unwrap(), &TaintOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn a_value_returned_by_a_method_of_another_file_is_tainted() {
+        let util = r#"
+package com.example.web;
+
+class Strings {
+    static String normalize(String value) {
+        return value.trim().toLowerCase();
+    }
+}
+"#;
+        let controller = controller(r#"response.getWriter().println(Strings.normalize(request.getParameter("q")));"#);
+        let findings = scan_project(&[("UserServlet.java", &controller), ("Strings.java", util)], &RuleSet::bundled().unwrap(), &TaintOptions::default());
+
+        let findings: Vec<(&str, &str)> = findings.iter().map(|f| (f.vulnerability.as_str(), f.function.as_str())).collect();
+        assert_eq!(findings, vec![("xss", "doGet")]);
+    }
+
+    /// A function returning its parameter, called with a tainted value and with a constant.
+    const IDENTITY: &str = r#"
+import java.sql.*;
+import javax.servlet.http.*;
+
+class Test extends HttpServlet {
+    Statement statement;
+
+    protected void doGet(HttpServletRequest request, HttpServletResponse response) throws Exception {
+        String name = identity(request.getParameter("name"));
+        String query = identity("select * from users");
+        statement.executeQuery(query);
+        response.getWriter().println(name);
+    }
+
+    String identity(String value) {
+        return value;
+    }
+}
+"#;
+
+    fn scan_vulnerabilities(code: &str, context_depth: usize) -> Vec<String> {
+        let findings = scan_project(&[("Test.java", code)], &RuleSet::bundled().unwrap(), &TaintOptions { context_depth });
+        let mut vulnerabilities: Vec<String> = findings.into_iter().map(|f| f.vulnerability).collect();
+        vulnerabilities.sort();
+        vulnerabilities
+    }
+
+    #[test]
+    fn the_values_returned_by_a_function_go_to_their_call_with_a_context() {
+        assert_eq!(scan_vulnerabilities(IDENTITY, 1), vec!["xss"]);
+    }
+
+    #[test]
+    fn the_values_returned_by_a_function_go_to_all_its_calls_without_a_context() {
+        assert_eq!(scan_vulnerabilities(IDENTITY, 0), vec!["sql-injection", "xss"]);
+    }
+
+    /// The identity function called through a wrapper: two call sites between the
+    /// tainted value and the returned value.
+    const WRAPPED_IDENTITY: &str = r#"
+import java.sql.*;
+import javax.servlet.http.*;
+
+class Test extends HttpServlet {
+    Statement statement;
+
+    protected void doGet(HttpServletRequest request, HttpServletResponse response) throws Exception {
+        String name = wrap(request.getParameter("name"));
+        String query = wrap("select * from users");
+        statement.executeQuery(query);
+        response.getWriter().println(name);
+    }
+
+    String wrap(String value) {
+        return identity(value);
+    }
+
+    String identity(String value) {
+        return value;
+    }
+}
+"#;
+
+    #[test]
+    fn the_values_returned_through_a_wrapper_go_to_all_the_calls_with_one_call_site() {
+        assert_eq!(scan_vulnerabilities(WRAPPED_IDENTITY, 1), vec!["sql-injection", "xss"]);
+    }
+
+    #[test]
+    fn the_values_returned_through_a_wrapper_go_to_their_call_with_two_call_sites() {
+        assert_eq!(scan_vulnerabilities(WRAPPED_IDENTITY, 2), vec!["xss"]);
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use tree_sitter::{Parser, Tree};
+
+use crate::dataflow::callgraph::build_call_graph;
+use crate::dataflow::java::build_project_graph;
+use crate::dataflow::languages::Language;
+use crate::dataflow::library::Library;
+use crate::dataflow::rules::RuleSet;
+use crate::dataflow::taint::{find_tainted_flows, TaintOptions};
+
+pub fn parse_java(code: &str) -> Tree {
+    let mut parser = Parser::new();
+    parser.set_language(&Language::Java.get_tree_sitter_language()).expect("the Java grammar is bundled");
+    parser.parse(code, None).expect("the parser has a language")
+}
+
+/// Returns an empty directory for a test, in the temporary directory.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("dataflow-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).expect("the temporary directory is writable");
+    directory
+}
+
+/// Returns the bundled library, loaded once for all the tests.
+pub fn bundled_library() -> &'static Library {
+    static LIBRARY: OnceLock<Library> = OnceLock::new();
+    LIBRARY.get_or_init(|| Library::bundled().expect("the bundled library is valid"))
+}
+
+/// A finding of a test file: the vulnerability, the label of the value, the function
+/// and the line of the sink and the names of the nodes of its path.
+#[derive(Debug, PartialEq)]
+pub struct TestFinding {
+    pub vulnerability: String,
+    pub label: String,
+    pub function: String,
+    pub line: usize,
+    pub path: Vec<String>,
+}
+
+/// Returns the findings of a Java file with the rules, the summaries of the rules
+/// being added to the bundled library.
+pub fn scan_java(code: &str, rules: &RuleSet) -> Vec<TestFinding> {
+    scan_project(&[("Test.java", code)], rules, &TaintOptions::default())
+}
+
+/// Returns the findings of several Java files (path and code) analyzed together, like
+/// the files of a project.
+pub fn scan_project(files: &[(&str, &str)], rules: &RuleSet, options: &TaintOptions) -> Vec<TestFinding> {
+    let mut library = Library::bundled().expect("the bundled library is valid");
+    rules.add_to(&mut library);
+    let trees: Vec<Tree> = files.iter().map(|(_, code)| parse_java(code)).collect();
+    let files: Vec<(&str, &str, &Tree)> = files.iter().zip(&trees).map(|((path, code), tree)| (*path, *code, tree)).collect();
+    let dataflow = build_project_graph(&files, &library);
+    let call_graph = build_call_graph(&dataflow);
+    find_tainted_flows(&dataflow, &call_graph, rules, &library, options)
+        .iter()
+        .map(|f| TestFinding {
+            vulnerability: f.sink_rule.clone(),
+            label: f.label.clone(),
+            function: f.function.clone(),
+            line: f.sink.get_position().0,
+            path: f.path.iter().map(|s| s.node.name.clone().unwrap_or_default()).collect(),
+        })
+        .collect()
+}
@@ -1,123 +1,312 @@
-mod dataflow;
-
-use crate::dataflow::java::build_graph;
-use anyhow::Result;
-use derive_builder::Builder;
-use std::env;
-use std::process::exit;
-use std::time::Instant;
-use std::{collections::HashMap, path::PathBuf};
-use tree_sitter::{Node, Parser, Tree};
-use walkdir::WalkDir;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
+use anyhow::Context;
+use clap::Parser as _;
+use tracing::{info, warn};
+use tree_sitter::Parser;
+
+use dataflow_experiments::dataflow::errors::DataflowError;
+use dataflow_experiments::dataflow::files::{get_files, read_source, FileFilter, FilesConfig};
+use dataflow_experiments::dataflow::index::{get_modification_time, hash_content, index_file, CodeIndex, FileIndex, DEFAULT_INDEX_FILE};
+use dataflow_experiments::dataflow::languages::{Language, LanguageRegistry};
+use dataflow_experiments::dataflow::logging::init_logging;
+
+/// Parse all the files of a directory and write the index of their definitions and references.
+#[derive(clap::Parser)]
+#[command(name = "index-code")]
+struct Cli {
+    /// File of the index, .dataflow-index in the directory by default
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Number of threads parsing the files, the number of CPUs by default
+    #[arg(short, long)]
+    jobs: Option<NonZeroUsize>,
+
+    /// Parse all the files again instead of only the files changed since the last index
+    #[arg(long)]
+    full: bool,
+
+    /// Level or filter of the logs, also read from the DATAFLOW_LOG environment variable
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<String>,
+
+    directory: String,
+}
+
+/// The parsers of a thread, created for the languages of its files.
+#[derive(Default)]
 struct Parsers {
-    java: Parser,
-    python: Parser,
-    go: Parser,
+    parsers: HashMap<Language, Parser>,
 }
 
-fn initialize_parsers() -> Parsers {
-    let mut java_parser = Parser::new();
-    java_parser
-        .set_language(&tree_sitter_java::language())
-        .expect("error while loading Java language");
-    let mut go_parser = Parser::new();
-    go_parser
-        .set_language(&tree_sitter_go::language())
-        .expect("error while loading go");
-    let mut go_parser = Parser::new();
-    go_parser
-        .set_language(&tree_sitter_go::language())
-        .expect("error while loading go");
-    let mut python_parser = Parser::new();
-    python_parser
-        .set_language(&tree_sitter_python::language())
-        .expect("error while loading python");
-    Parsers {
-        java: java_parser,
-        python: python_parser,
-        go: go_parser,
+impl Parsers {
+    fn get(&mut self, language: Language) -> Result<&mut Parser, DataflowError> {
+        match self.parsers.entry(language) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let mut parser = Parser::new();
+                parser.set_language(&language.get_tree_sitter_language()).map_err(|source| DataflowError::Language {
+                    language: language.get_name().to_string(),
+                    source,
+                })?;
+                Ok(entry.insert(parser))
+            }
+        }
     }
 }
 
-fn parse_file(path: &PathBuf, parsers: &mut Parsers) -> Option<Tree> {
-    // read filename into a string
-    let source_code = std::fs::read_to_string(path);
+/// How a file changed since the previous index.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum FileStatus {
+    UNCHANGED,
+    CHANGED,
+    ADDED,
+}
+
+/// The index of a file with the time spent reading, parsing and indexing it.
+struct ParsedFile {
+    language: Language,
+    index: FileIndex,
+    status: FileStatus,
+    duration: Duration,
+}
 
-    if source_code.is_err() {
-        return None;
+/// Parse a file, returns None for the files in no known language. The file is only
+/// parsed if it changed since its previous index: its size or modification time changed
+/// and so did the hash of its content.
+fn parse_file(path: &Path, registry: &LanguageRegistry, parsers: &mut Parsers, previous: Option<&FileIndex>) -> Result<Option<ParsedFile>, DataflowError> {
+    let Some(language) = registry.detect(path) else {
+        return Ok(None);
+    };
+    let start = Instant::now();
+    let filename = path.to_string_lossy().to_string();
+    let metadata = std::fs::metadata(path).map_err(|source| DataflowError::Io { path: filename.clone(), source })?;
+    let unchanged = |index: FileIndex| ParsedFile {
+        language,
+        index,
+        status: FileStatus::UNCHANGED,
+        duration: start.elapsed(),
+    };
+    if let Some(previous) = previous.filter(|p| p.is_up_to_date(&metadata)) {
+        return Ok(Some(unchanged(previous.clone())));
     }
 
-    if let Some(ext) = path.extension() {
-        if ext == "go" {
-            return parsers.go.parse(source_code.unwrap(), None);
-        }
-        if ext == "java" {
-            return parsers.java.parse(source_code.unwrap(), None);
-        }
-        if ext == "python" {
-            return parsers.python.parse(source_code.unwrap(), None);
-        }
+    let source_code = read_source(&filename)?;
+    // the file was touched but its content is the same
+    if let Some(previous) = previous.filter(|p| p.hash == hash_content(source_code.as_bytes())) {
+        return Ok(Some(unchanged(FileIndex {
+            size: metadata.len(),
+            modified: get_modification_time(&metadata),
+            ..previous.clone()
+        })));
     }
 
-    return None;
+    let Some(tree) = parsers.get(language)?.parse(&source_code, None) else {
+        return Err(DataflowError::Parse { path: filename });
+    };
+    Ok(Some(ParsedFile {
+        language,
+        index: index_file(&filename, language.get_name(), &tree, &source_code, &metadata),
+        status: if previous.is_some() { FileStatus::CHANGED } else { FileStatus::ADDED },
+        duration: start.elapsed(),
+    }))
+}
+
+/// Parse the files with a pool of threads, each with its own parsers. The results
+/// are in the order of the files.
+fn parse_files(files: &[PathBuf], registry: &LanguageRegistry, previous: &HashMap<&str, &FileIndex>, jobs: usize) -> Vec<Result<Option<ParsedFile>, DataflowError>> {
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(files.len()).max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut parsers = Parsers::default();
+                    let mut results = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(file) = files.get(index) else {
+                            break;
+                        };
+                        let previous_index = previous.get(file.to_string_lossy().as_ref()).copied();
+                        results.push((index, parse_file(file, registry, &mut parsers, previous_index)));
+                    }
+                    results
+                })
+            })
+            .collect();
+
+        let mut results = vec![];
+        for worker in workers {
+            results.extend(worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)));
+        }
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    })
 }
 
-pub fn get_files(directory: &str) -> Result<Vec<PathBuf>> {
-    let mut files_to_return: Vec<PathBuf> = vec![];
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Err(error) = init_logging(cli.log_level.as_deref(), 0) {
+        eprintln!("error: invalid log level: {:#}", error);
+        return ExitCode::from(2);
+    }
+    let directory = &cli.directory;
+    let jobs = cli.jobs.or(std::thread::available_parallelism().ok()).map_or(1, |j| j.get());
+    info!(directory = directory.as_str(), jobs, "indexing");
+    let mut indexes: Vec<FileIndex> = Vec::new();
+    let mut failures: Vec<anyhow::Error> = Vec::new();
+    // number of files and time spent parsing them by language, summed over the threads
+    let mut timings: BTreeMap<Language, (usize, Duration)> = BTreeMap::new();
 
-    for entry in WalkDir::new(directory) {
-        let dir_entry = entry?;
-        let entry = dir_entry.path();
+    let files = match get_files(directory, &FileFilter::default()).with_context(|| format!("cannot list the files of {}", directory)) {
+        Ok(files) => files,
+        Err(error) => {
+            eprintln!("error: {:#}", error);
+            return ExitCode::from(2);
+        }
+    };
+    let registry = match FilesConfig::load(Path::new(directory)) {
+        Ok(config) => LanguageRegistry::new(&config.languages),
+        Err(error) => {
+            eprintln!("error: {:#}", anyhow::Error::new(error));
+            return ExitCode::from(2);
+        }
+    };
 
-        // we only include if this is a file and not a symlink
-        // we should NEVER follow symlink for security reason (an attacker could then
-        // attempt to add a symlink outside the repo and read content outside of the
-        // repo with a custom rule.
-        let mut should_include = entry.is_file() && !entry.is_symlink();
-        let path_buf = entry.to_path_buf();
+    let output = cli.output.clone().unwrap_or(Path::new(directory).join(DEFAULT_INDEX_FILE));
+    let previous_index = match (cli.full, output.exists()) {
+        (false, true) => CodeIndex::load(&output).unwrap_or_else(|error| {
+            warn!("{:#}, all the files are indexed again", anyhow::Error::new(error));
+            CodeIndex::default()
+        }),
+        _ => CodeIndex::default(),
+    };
+    let previous: HashMap<&str, &FileIndex> = previous_index.files.iter().map(|f| (f.path.as_str(), f)).collect();
+    // number of files by status
+    let mut statuses: HashMap<FileStatus, usize> = HashMap::new();
 
-        let relative_path_str = path_buf
-            .strip_prefix(directory)
-            .ok()
-            .and_then(|p| p.to_str())
-            .ok_or_else(|| anyhow::Error::msg("should get the path"))?;
+    let start = Instant::now();
 
-        if should_include {
-            files_to_return.push(entry.to_path_buf());
+    let results = parse_files(&files, &registry, &previous, jobs);
+    for result in results {
+        match result {
+            Ok(Some(parsed)) => {
+                *statuses.entry(parsed.status).or_default() += 1;
+                if parsed.status != FileStatus::UNCHANGED {
+                    let timing = timings.entry(parsed.language).or_default();
+                    timing.0 += 1;
+                    timing.1 += parsed.duration;
+                }
+                indexes.push(parsed.index);
+            }
+            Ok(None) => {}
+            Err(error) => {
+                let error = anyhow::Error::new(error);
+                warn!("{:#}, the file is skipped", error);
+                failures.push(error);
+            }
         }
     }
 
-    Ok(files_to_return)
+    let elapsed = start.elapsed();
+    let indexed: HashSet<&str> = indexes.iter().map(|f| f.path.as_str()).collect();
+    let deleted = previous.keys().filter(|p| !indexed.contains(*p)).count();
+    // the symbols are built again from the files, the definitions of the changed and deleted files are replaced
+    let index = CodeIndex::new(indexes);
+    if let Err(error) = index.save(&output) {
+        eprintln!("error: {:#}", anyhow::Error::new(error));
+        return ExitCode::from(2);
+    }
+
+    println!(
+        "took {:.2?} to index {} files with {} jobs ({} files could not be parsed): {} definitions in {}",
+        elapsed,
+        index.files.len(),
+        jobs,
+        failures.len(),
+        index.symbols.values().map(|s| s.len()).sum::<usize>(),
+        output.display()
+    );
+    println!(
+        "  {} unchanged, {} changed, {} added, {} deleted",
+        statuses.get(&FileStatus::UNCHANGED).unwrap_or(&0),
+        statuses.get(&FileStatus::CHANGED).unwrap_or(&0),
+        statuses.get(&FileStatus::ADDED).unwrap_or(&0),
+        deleted
+    );
+    for (language, (count, duration)) in &timings {
+        println!("  {}: {} files parsed in {:.2?}", language.get_name(), count, duration);
+    }
+    for failure in &failures {
+        eprintln!("error: {:#}", failure);
+    }
+
+    match failures.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::from(2),
+    }
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let directory = &args[1];
-    println!("directory: {}", directory);
-    let mut all_trees: Vec<Tree> = Vec::new();
-    let mut parsers = initialize_parsers();
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::SystemTime;
 
-    let files = get_files(&directory).expect("cannot get files");
+    use super::*;
 
-    let start = Instant::now();
+    /// Returns an empty directory for a test, in the temporary directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("index-code-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
 
-    for file in files {
-        let tree = parse_file(&file, &mut parsers);
-        if tree.is_none() {
-            // eprintln!("no tree for file {}", file.to_str().unwrap_or("default"));
-        } else {
-            // println!("got tree for file {}", file.to_str().unwrap_or("default"));
-            all_trees.push(tree.unwrap());
-        }
+    fn parse(path: &Path, previous: Option<&FileIndex>) -> ParsedFile {
+        parse_file(path, &LanguageRegistry::default(), &mut Parsers::default(), previous).unwrap().unwrap()
     }
 
-    let elapsed_secs = start.elapsed().as_secs();
+    fn set_modified(path: &Path, seconds: u64) {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(seconds);
+        File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+    }
 
-    println!(
-        "took {} to get {} trees, waiting 200 seconds",
-        elapsed_secs,
-        all_trees.len()
-    );
-    std::thread::sleep(std::time::Duration::from_secs(200));
+    #[test]
+    fn a_file_is_parsed_again_only_when_its_content_changed() {
+        let path = temp_dir("incremental").join("Task.java");
+        std::fs::write(&path, "class Task { void run() {} }").unwrap();
+        set_modified(&path, 1_000);
+        let added = parse(&path, None);
+        assert_eq!(added.status, FileStatus::ADDED);
+
+        let unchanged = parse(&path, Some(&added.index));
+        assert_eq!(unchanged.status, FileStatus::UNCHANGED);
+
+        // touched: the modification time changed but not the content
+        set_modified(&path, 2_000);
+        let touched = parse(&path, Some(&unchanged.index));
+        assert_eq!(touched.status, FileStatus::UNCHANGED);
+        assert_eq!(touched.index.modified, 2_000_000_000_000);
+
+        std::fs::write(&path, "class Task { void run() {} void stop() {} }").unwrap();
+        let changed = parse(&path, Some(&touched.index));
+        assert_eq!(changed.status, FileStatus::CHANGED);
+        assert_eq!(changed.index.definitions.len(), 3);
+    }
+
+    #[test]
+    fn the_files_in_no_known_language_are_not_indexed() {
+        let path = temp_dir("unknown-language").join("notes.txt");
+        std::fs::write(&path, "notes").unwrap();
+
+        assert!(parse_file(&path, &LanguageRegistry::default(), &mut Parsers::default(), None).unwrap().is_none());
+    }
 }